@@ -0,0 +1,403 @@
+use crate::diplomacy::Diplomacy;
+use crate::materials::{Asteroid, ResourceCost, ResourceLedger};
+use crate::movement::MoveForward;
+use crate::selection::{CurrentlySelected, Team};
+use crate::units::{
+    spawn_explosion_visual, Health, HealthBar, Shield, ShieldBar, UnitCommand, UnitCommandList,
+};
+use crate::GamePhase;
+use crate::MainCamera;
+use crate::MapBoundaries;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+pub struct AbilitiesPlugin;
+
+impl Plugin for AbilitiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                tick_ability_cooldowns,
+                ability_hotkeys,
+                resolve_ability_requests,
+                pull_tractored_asteroids,
+                attach_burst_shield_bars,
+                expire_burst_shields,
+            )
+                .run_if(in_state(GamePhase::Playing)),
+        );
+        app.add_event::<AbilityRequest>();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AbilityKind {
+    WarpJump {
+        max_distance: f32,
+    },
+    ShieldBurst {
+        radius: f32,
+        amount: f32,
+        duration: f32,
+    },
+    TractorBeam {
+        range: f32,
+        pull_speed: f32,
+        duration: f32,
+    },
+    //Pushes drifting asteroids away from the caster
+    Nudge {
+        radius: f32,
+        speed: f32,
+    },
+}
+
+pub struct Ability {
+    pub name: &'static str,
+    pub kind: AbilityKind,
+    pub cooldown: Timer,
+    pub cost: ResourceCost,
+    pub hotkey: KeyCode,
+}
+
+impl Ability {
+    //Abilities start off cooldown
+    pub fn new(
+        name: &'static str,
+        kind: AbilityKind,
+        cooldown_secs: f32,
+        cost: ResourceCost,
+        hotkey: KeyCode,
+    ) -> Ability {
+        let mut cooldown = Timer::from_seconds(cooldown_secs, TimerMode::Once);
+        cooldown.tick(std::time::Duration::from_secs_f32(cooldown_secs));
+        Ability {
+            name,
+            kind,
+            cooldown,
+            cost,
+            hotkey,
+        }
+    }
+
+    pub fn hotkey_label(&self) -> String {
+        format!("{:?}", self.hotkey).replace("Key", "")
+    }
+}
+
+#[derive(Component)]
+pub struct Abilities(pub Vec<Ability>);
+
+//Sent by hotkeys and HUD buttons, resolved in one place
+#[derive(Event)]
+pub struct AbilityRequest {
+    pub caster: Entity,
+    pub slot: usize,
+    pub target_pos: Option<Vec3>,
+}
+
+#[derive(Component)]
+pub struct TractorBeam {
+    pub target: Entity,
+    pub pull_speed: f32,
+    pub timer: Timer,
+}
+
+//Shield granted by a shield burst to a unit that has none of its own
+#[derive(Component)]
+pub struct BurstShield {
+    pub timer: Timer,
+}
+
+//Bar of a burst shield, goes away with the shield
+#[derive(Component)]
+pub struct BurstShieldBar;
+
+fn tick_ability_cooldowns(time: Res<Time>, mut abilities_q: Query<&mut Abilities>) {
+    for mut abilities in abilities_q.iter_mut() {
+        for ability in abilities.0.iter_mut() {
+            ability.cooldown.tick(time.delta());
+        }
+    }
+}
+
+fn ability_hotkeys(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    currently_selected: Res<CurrentlySelected>,
+    abilities_q: Query<&Abilities>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut ev_request: EventWriter<AbilityRequest>,
+) {
+    let (camera, camera_transform) = q_camera.single();
+    let window = q_window.single();
+    let cursor_pos = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+        .map(|ray| ray.origin.truncate().extend(0.));
+
+    for e in currently_selected.ent.iter() {
+        if let Ok(abilities) = abilities_q.get(*e) {
+            for (slot, ability) in abilities.0.iter().enumerate() {
+                if keyboard_input.just_pressed(ability.hotkey) {
+                    ev_request.send(AbilityRequest {
+                        caster: *e,
+                        slot,
+                        target_pos: cursor_pos,
+                    });
+                }
+            }
+        }
+    }
+}
+
+type ShieldTargets<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Team,
+        Option<&'static mut Shield>,
+        Option<&'static mut BurstShield>,
+    ),
+    With<Health>,
+>;
+
+//Everything an ability can land on
+#[derive(SystemParam)]
+struct AbilityTargets<'w, 's> {
+    transforms: Query<'w, 's, &'static mut Transform>,
+    allies: ShieldTargets<'w, 's>,
+    asteroids: Query<'w, 's, (Entity, &'static mut MoveForward), With<Asteroid>>,
+    diplomacy: Res<'w, Diplomacy>,
+    boundaries: Res<'w, MapBoundaries>,
+}
+
+fn resolve_ability_requests(
+    mut cmd: Commands,
+    mut ev_request: EventReader<AbilityRequest>,
+    mut casters: Query<(&mut Abilities, &Team, Option<&UnitCommandList>)>,
+    targets: AbilityTargets,
+    mut ledger: ResMut<ResourceLedger>,
+    asset_server: Res<AssetServer>,
+) {
+    let AbilityTargets {
+        mut transforms,
+        mut allies,
+        mut asteroids,
+        diplomacy,
+        boundaries,
+    } = targets;
+    for request in ev_request.read() {
+        let Ok((mut abilities, caster_team, command_list)) = casters.get_mut(request.caster) else {
+            continue;
+        };
+        let Some(ability) = abilities.0.get_mut(request.slot) else {
+            continue;
+        };
+        if !ability.cooldown.finished() || !ledger.can_afford(&ability.cost) {
+            continue;
+        }
+        let Ok(caster_pos) = transforms.get(request.caster).map(|tr| tr.translation) else {
+            continue;
+        };
+
+        let mut used = false;
+        match ability.kind {
+            AbilityKind::WarpJump { max_distance } => {
+                //Jump towards the cursor, or towards the current move order when cast from the HUD
+                let mut destination = request.target_pos;
+                if destination.is_none() {
+                    if let Some(command_list) = command_list {
+                        for c in command_list.commands.iter() {
+                            if let UnitCommand::MoveToPos(pos) = c {
+                                destination = Some(*pos);
+                                break;
+                            }
+                        }
+                    }
+                }
+                if let Some(destination) = destination {
+                    let diff = (destination - caster_pos).truncate().extend(0.);
+                    let jump = diff.normalize_or_zero() * diff.length().min(max_distance);
+                    //Never leave the play area, even when cast at the edge of the map
+                    let landing = Vec3::new(
+                        (caster_pos.x + jump.x)
+                            .clamp(boundaries.x_boundaries.x, boundaries.x_boundaries.y),
+                        (caster_pos.y + jump.y)
+                            .clamp(boundaries.y_boundaries.x, boundaries.y_boundaries.y),
+                        caster_pos.z,
+                    );
+                    if let Ok(mut caster_tr) = transforms.get_mut(request.caster) {
+                        caster_tr.translation = landing;
+                    }
+                    spawn_explosion_visual(&mut cmd, &asset_server, caster_pos, 80.);
+                    spawn_explosion_visual(&mut cmd, &asset_server, landing, 80.);
+                    used = true;
+                }
+            }
+            AbilityKind::ShieldBurst {
+                radius,
+                amount,
+                duration,
+            } => {
+                for (ally_e, ally_team, shield, burst) in allies.iter_mut() {
+                    if !diplomacy.is_ally(caster_team.0, ally_team.0) {
+                        continue;
+                    }
+                    let Ok(ally_tr) = transforms.get(ally_e) else {
+                        continue;
+                    };
+                    if (ally_tr.translation - caster_pos).length() > radius {
+                        continue;
+                    }
+                    if let Some(mut shield) = shield {
+                        shield.current = shield.capacity;
+                        //Bursting again keeps a borrowed shield for the full duration
+                        if let Some(mut burst) = burst {
+                            burst.timer.reset();
+                        }
+                    } else {
+                        cmd.entity(ally_e).try_insert((
+                            Shield {
+                                current: amount,
+                                capacity: amount,
+                                regen_rate: 0.,
+                                regen_delay: Timer::from_seconds(duration, TimerMode::Once),
+                            },
+                            BurstShield {
+                                timer: Timer::from_seconds(duration, TimerMode::Once),
+                            },
+                        ));
+                    }
+                }
+                spawn_explosion_visual(&mut cmd, &asset_server, caster_pos, radius);
+                used = true;
+            }
+            AbilityKind::TractorBeam {
+                range,
+                pull_speed,
+                duration,
+            } => {
+                let mut closest: (Option<Entity>, f32) = (None, range);
+                for (asteroid_e, _) in asteroids.iter() {
+                    if let Ok(asteroid_tr) = transforms.get(asteroid_e) {
+                        let dist = (asteroid_tr.translation - caster_pos).length();
+                        if dist < closest.1 {
+                            closest = (Some(asteroid_e), dist);
+                        }
+                    }
+                }
+                if let Some(target) = closest.0 {
+                    cmd.entity(request.caster).insert(TractorBeam {
+                        target,
+                        pull_speed,
+                        timer: Timer::from_seconds(duration, TimerMode::Once),
+                    });
+                    used = true;
+                }
+            }
+            AbilityKind::Nudge { radius, speed } => {
+                for (asteroid_e, mut move_forward) in asteroids.iter_mut() {
+                    let Ok(mut asteroid_tr) = transforms.get_mut(asteroid_e) else {
+                        continue;
+                    };
+                    let away = (asteroid_tr.translation - caster_pos).truncate();
+                    if away.length() > radius {
+                        continue;
+                    }
+                    asteroid_tr.rotation = Quat::from_rotation_z(away.to_angle());
+                    move_forward.speed = move_forward.speed.max(speed);
+                }
+                spawn_explosion_visual(&mut cmd, &asset_server, caster_pos, radius);
+                used = true;
+            }
+        }
+
+        if used {
+            ability.cooldown.reset();
+            ledger.spend(&ability.cost);
+        }
+    }
+}
+
+fn pull_tractored_asteroids(
+    mut cmd: Commands,
+    time: Res<Time>,
+    mut beams: Query<(Entity, &mut TractorBeam)>,
+    mut transforms: Query<&mut Transform>,
+    mut gizmos: Gizmos,
+) {
+    for (caster_e, mut beam) in beams.iter_mut() {
+        beam.timer.tick(time.delta());
+        let Ok([caster_tr, mut target_tr]) = transforms.get_many_mut([caster_e, beam.target])
+        else {
+            cmd.entity(caster_e).remove::<TractorBeam>();
+            continue;
+        };
+        let diff = (caster_tr.translation - target_tr.translation)
+            .truncate()
+            .extend(0.);
+        if beam.timer.finished() || diff.length() < 150. {
+            cmd.entity(caster_e).remove::<TractorBeam>();
+            continue;
+        }
+        target_tr.translation += diff.normalize_or_zero() * beam.pull_speed * time.delta_seconds();
+        gizmos.line(
+            caster_tr.translation,
+            target_tr.translation,
+            Color::srgba(0.4, 0.8, 1., 0.6),
+        );
+    }
+}
+
+//Units without a shield of their own have no shield bar, give them one under the health bar
+fn attach_burst_shield_bars(
+    mut cmd: Commands,
+    new_bursts: Query<(Entity, &Children), Added<BurstShield>>,
+    healthbars: Query<&Transform, With<HealthBar>>,
+    asset_server: Res<AssetServer>,
+) {
+    for (e, children) in new_bursts.iter() {
+        let bar_pos = children
+            .iter()
+            .find_map(|c| healthbars.get(*c).ok())
+            .map(|tr| tr.translation - Vec3::new(0., 10., 0.))
+            .unwrap_or(Vec3::new(0., -40., 0.));
+        let bar = cmd
+            .spawn(SpriteBundle {
+                texture: asset_server.load("healthbar.png"),
+                transform: Transform::from_translation(bar_pos),
+                sprite: Sprite {
+                    color: Color::srgba(0., 0.6, 1., 1.),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(ShieldBar)
+            .insert(BurstShieldBar)
+            .id();
+        cmd.entity(e).add_child(bar);
+    }
+}
+
+fn expire_burst_shields(
+    mut cmd: Commands,
+    time: Res<Time>,
+    mut burst_q: Query<(Entity, &mut BurstShield, Option<&Children>)>,
+    bars: Query<(), With<BurstShieldBar>>,
+) {
+    for (e, mut burst, children) in burst_q.iter_mut() {
+        burst.timer.tick(time.delta());
+        if burst.timer.finished() {
+            cmd.entity(e).remove::<(Shield, BurstShield)>();
+            for c in children.into_iter().flatten() {
+                if bars.contains(*c) {
+                    cmd.entity(*c).despawn_recursive();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::diplomacy::LocalPlayer;
+use crate::materials::ResourceLedger;
+use crate::objectives::Scenario;
+use crate::research::{Research, Upgrade};
+use crate::selection::Team;
+use crate::units::{spawn_ally, MotherUnit, SupplyExempt, UnitArchetype, Veterancy};
+use crate::AppState;
+use crate::GameMode;
+use crate::GamePhase;
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+pub struct CampaignPlugin;
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            restore_campaign_fleet.run_if(in_state(GamePhase::Playing)),
+        );
+        app.add_systems(OnEnter(GamePhase::Won), save_campaign_progress);
+        app.add_systems(OnEnter(GamePhase::Lost), end_campaign);
+        app.add_systems(OnEnter(AppState::Menu), restore_menu_choice);
+        app.init_resource::<Campaign>();
+    }
+}
+
+//Missions of a campaign, in order
+pub const CAMPAIGN_MISSIONS: [Scenario; 4] = [
+    Scenario::AsteroidField,
+    Scenario::Convoy,
+    Scenario::HoldTheLine,
+    Scenario::PirateBase,
+];
+
+//A surviving ship waiting for the next mission
+pub struct SavedShip {
+    pub archetype: UnitArchetype,
+    pub xp: f32,
+}
+
+//Lives outside of the game state, so despawn_everything and the reset systems never touch it
+#[derive(Resource, Default)]
+pub struct Campaign {
+    pub active: bool,
+    pub mission: usize,
+    pub fleet: Vec<SavedShip>,
+    pub ledger: ResourceLedger,
+    pub upgrades: HashSet<Upgrade>,
+    //Set when a mission starts, the fleet is brought back on its first frame
+    pub pending_restore: bool,
+    //Mode and scenario picked in the main menu before a mission took them over
+    pub menu_choice: Option<(GameMode, Scenario)>,
+}
+
+impl Campaign {
+    pub fn start() -> Campaign {
+        Campaign {
+            active: true,
+            ..default()
+        }
+    }
+
+    pub fn current_scenario(&self) -> Scenario {
+        CAMPAIGN_MISSIONS[self.mission.min(CAMPAIGN_MISSIONS.len() - 1)]
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.mission >= CAMPAIGN_MISSIONS.len()
+    }
+}
+
+//Runs once the reset systems are done, so the carried over state isn't wiped again
+fn restore_campaign_fleet(
+    mut cmd: Commands,
+    mut campaign: ResMut<Campaign>,
+    mut ledger: ResMut<ResourceLedger>,
+    mut research: ResMut<Research>,
+    mother_unit: Query<(&Transform, &Team), With<MotherUnit>>,
+    local_player: Res<LocalPlayer>,
+    asset_server: Res<AssetServer>,
+) {
+    if !campaign.active || !campaign.pending_restore {
+        return;
+    }
+    campaign.pending_restore = false;
+    if campaign.mission == 0 {
+        return;
+    }
+    ledger.ore = campaign.ledger.ore;
+    ledger.ice = campaign.ledger.ice;
+    ledger.crystal = campaign.ledger.crystal;
+    research.completed = campaign.upgrades.clone();
+
+    let Some((mother_tr, _)) = mother_unit
+        .iter()
+        .find(|(_, team)| team.0 == local_player.0)
+    else {
+        return;
+    };
+    let columns = 6;
+    for (i, ship) in campaign.fleet.iter().enumerate() {
+        let offset = Vec3::new(
+            ((i % columns) as f32 - columns as f32 / 2.) * 70.,
+            -120. - (i / columns) as f32 * 70.,
+            0.,
+        );
+        let spawn_pos = mother_tr.translation + offset;
+        if let Some(e) = spawn_ally(&mut cmd, ship.archetype, spawn_pos, &asset_server) {
+            //Ranks and their bonuses come back through rank_up_units
+            //The mothership alone can't hold a veteran fleet, so it doesn't count against supply
+            cmd.entity(e).insert((
+                Veterancy {
+                    xp: ship.xp,
+                    rank: 0,
+                },
+                SupplyExempt,
+            ));
+        }
+    }
+}
+
+pub fn save_campaign_progress(
+    mut campaign: ResMut<Campaign>,
+    units: Query<(&UnitArchetype, &Team, Option<&Veterancy>)>,
+    ledger: Res<ResourceLedger>,
+    research: Res<Research>,
+    local_player: Res<LocalPlayer>,
+) {
+    if !campaign.active {
+        return;
+    }
+    //Only ships the mothership can build carry over, they are the ones using supply
+    campaign.fleet = units
+        .iter()
+        .filter(|(archetype, team, _)| team.0 == local_player.0 && archetype.supply() > 0)
+        .map(|(archetype, _, veterancy)| SavedShip {
+            archetype: *archetype,
+            xp: veterancy.map(|v| v.xp).unwrap_or(0.),
+        })
+        .collect();
+    campaign.ledger = ResourceLedger {
+        ore: ledger.ore,
+        ice: ledger.ice,
+        crystal: ledger.crystal,
+        ..default()
+    };
+    campaign.upgrades = research.completed.clone();
+    campaign.mission += 1;
+    if campaign.is_finished() {
+        campaign.active = false;
+    }
+}
+
+//Hands the main menu back the mode and scenario it had before the campaign
+fn restore_menu_choice(
+    mut campaign: ResMut<Campaign>,
+    mut game_mode: ResMut<GameMode>,
+    mut scenario: ResMut<Scenario>,
+) {
+    if let Some((mode, menu_scenario)) = campaign.menu_choice.take() {
+        *game_mode = mode;
+        *scenario = menu_scenario;
+    }
+}
+
+//Losing the mothership loses the whole campaign
+fn end_campaign(mut campaign: ResMut<Campaign>) {
+    campaign.active = false;
+}
@@ -0,0 +1,145 @@
+use crate::objectives::PirateBase;
+use crate::units::{AttackComponent, EmpAbility, Hardpoints, Health, Shield, UnitArchetype};
+use crate::GamePhase;
+use bevy::prelude::*;
+
+pub struct DifficultyPlugin;
+
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            scale_pirate_stats.run_if(in_state(GamePhase::Playing)),
+        );
+        app.init_resource::<Difficulty>();
+    }
+}
+
+//Picked in the main menu, read by the pirate director and the game resets
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn from_label(label: &str) -> Option<Difficulty> {
+        Difficulty::ALL.into_iter().find(|d| d.label() == label)
+    }
+
+    pub fn next(&self) -> Difficulty {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    //Seconds between pirate waves
+    pub fn wave_interval(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 30.,
+            Difficulty::Normal => 20.,
+            Difficulty::Hard => 14.,
+        }
+    }
+
+    //Pirate ships added with every wave
+    pub fn wave_size_growth(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.7,
+            Difficulty::Normal => 1.,
+            Difficulty::Hard => 1.4,
+        }
+    }
+
+    //Scales pirate health and damage
+    pub fn enemy_stat_multiplier(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.75,
+            Difficulty::Normal => 1.,
+            Difficulty::Hard => 1.3,
+        }
+    }
+
+    pub fn starting_ore(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 100.,
+            Difficulty::Normal => 50.,
+            Difficulty::Hard => 25.,
+        }
+    }
+
+    //Seconds until the extraction point shows up. Harder runs have to hold out longer on purpose,
+    //the extra waves that brings are what lets a hard run outscore an easy one
+    pub fn extraction_time(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 60. * 4.,
+            Difficulty::Normal => 60. * 5.,
+            Difficulty::Hard => 60. * 6.,
+        }
+    }
+}
+
+type NewPirates<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Option<&'static UnitArchetype>,
+        Has<PirateBase>,
+        &'static mut Health,
+        Option<&'static mut AttackComponent>,
+        Option<&'static mut Hardpoints>,
+        Option<&'static mut Shield>,
+        Option<&'static mut EmpAbility>,
+    ),
+    Or<(Added<UnitArchetype>, Added<PirateBase>)>,
+>;
+
+//Boss drones are pirate melee ships, so they get scaled when they launch like any other pirate
+fn scale_pirate_stats(difficulty: Res<Difficulty>, mut new_units: NewPirates) {
+    let multiplier = difficulty.enemy_stat_multiplier();
+    for (archetype, is_base, mut hp, attack_comp, hardpoints, shield, emp) in new_units.iter_mut() {
+        let is_pirate_ship = matches!(
+            archetype,
+            Some(
+                UnitArchetype::PirateMelee
+                    | UnitArchetype::PirateRanged
+                    | UnitArchetype::PirateBomber
+                    | UnitArchetype::PirateBoss
+            )
+        );
+        if !is_pirate_ship && !is_base {
+            continue;
+        }
+        hp.max_health *= multiplier;
+        hp.current *= multiplier;
+        if let Some(mut attack_comp) = attack_comp {
+            attack_comp.attack_amount *= multiplier;
+        }
+        if let Some(mut hardpoints) = hardpoints {
+            for hardpoint in hardpoints.0.iter_mut() {
+                hardpoint.weapon.attack_amount *= multiplier;
+            }
+        }
+        if let Some(mut shield) = shield {
+            shield.capacity *= multiplier;
+            shield.current *= multiplier;
+        }
+        if let Some(mut emp) = emp {
+            emp.stun_seconds *= multiplier;
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use crate::AppState;
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+pub struct DiplomacyPlugin;
+
+impl Plugin for DiplomacyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), reset_diplomacy);
+        app.init_resource::<Diplomacy>();
+        app.init_resource::<LocalPlayer>();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Relation {
+    Ally,
+    Neutral,
+    Hostile,
+}
+
+//The team the human player is controlling
+#[derive(Resource, Default)]
+pub struct LocalPlayer(pub i32);
+
+//Team pairs are stored with the lower team number first, so the table is symmetric
+#[derive(Resource)]
+pub struct Diplomacy {
+    pub relations: HashMap<(i32, i32), Relation>,
+    pub default_relation: Relation,
+}
+
+impl Default for Diplomacy {
+    fn default() -> Diplomacy {
+        Diplomacy {
+            relations: HashMap::new(),
+            default_relation: Relation::Hostile,
+        }
+    }
+}
+
+impl Diplomacy {
+    pub fn set_relation(&mut self, team_a: i32, team_b: i32, relation: Relation) {
+        self.relations
+            .insert((team_a.min(team_b), team_a.max(team_b)), relation);
+    }
+
+    pub fn relation(&self, team_a: i32, team_b: i32) -> Relation {
+        if team_a == team_b {
+            return Relation::Ally;
+        }
+        *self
+            .relations
+            .get(&(team_a.min(team_b), team_a.max(team_b)))
+            .unwrap_or(&self.default_relation)
+    }
+
+    pub fn is_hostile(&self, team_a: i32, team_b: i32) -> bool {
+        self.relation(team_a, team_b) == Relation::Hostile
+    }
+
+    pub fn is_ally(&self, team_a: i32, team_b: i32) -> bool {
+        self.relation(team_a, team_b) == Relation::Ally
+    }
+}
+
+fn reset_diplomacy(mut diplomacy: ResMut<Diplomacy>) {
+    *diplomacy = Diplomacy::default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn own_team_is_always_ally() {
+        let mut diplomacy = Diplomacy::default();
+        diplomacy.set_relation(2, 2, Relation::Hostile);
+        assert_eq!(diplomacy.relation(2, 2), Relation::Ally);
+    }
+
+    #[test]
+    fn unknown_pairs_use_the_default_relation() {
+        let mut diplomacy = Diplomacy::default();
+        assert_eq!(diplomacy.relation(0, 1), Relation::Hostile);
+        diplomacy.default_relation = Relation::Neutral;
+        assert_eq!(diplomacy.relation(0, 1), Relation::Neutral);
+    }
+
+    #[test]
+    fn relations_are_symmetric() {
+        let mut diplomacy = Diplomacy::default();
+        diplomacy.set_relation(3, 1, Relation::Ally);
+        assert_eq!(diplomacy.relation(1, 3), Relation::Ally);
+        assert_eq!(diplomacy.relation(3, 1), Relation::Ally);
+        assert!(diplomacy.is_ally(1, 3));
+        assert!(!diplomacy.is_hostile(3, 1));
+        assert_eq!(diplomacy.relation(1, 2), Relation::Hostile);
+    }
+}
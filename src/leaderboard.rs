@@ -0,0 +1,341 @@
+use crate::difficulty::Difficulty;
+use crate::objectives::Scenario;
+use crate::scoring::Score;
+use crate::GameMode;
+use crate::GamePhase;
+use crate::MapSeed;
+use bevy::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub struct LeaderboardPlugin;
+
+impl Plugin for LeaderboardPlugin {
+    fn build(&self, app: &mut App) {
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_systems(Startup, load_leaderboard);
+        app.add_systems(OnEnter(GamePhase::Won), record_win);
+        app.add_systems(OnEnter(GamePhase::Lost), record_loss);
+        app.add_systems(OnEnter(GamePhase::Survived), record_survival);
+        app.init_resource::<Leaderboard>();
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+const LEADERBOARD_FILE: &str = "leaderboard.txt";
+//Runs kept for every scenario and difficulty
+pub const ENTRIES_PER_TABLE: usize = 10;
+
+pub struct LeaderboardEntry {
+    pub mode: GameMode,
+    pub scenario: Scenario,
+    pub difficulty: Difficulty,
+    pub score: f32,
+    pub minerals: f32,
+    pub waves: i32,
+    pub date: String,
+    pub seed: u64,
+}
+
+impl LeaderboardEntry {
+    //Endless runs share a table whatever scenario was picked, it isn't played
+    pub fn table_name(&self) -> String {
+        let run = match self.mode {
+            GameMode::Extraction => self.scenario.label(),
+            GameMode::Endless => self.mode.label(),
+        };
+        format!("{} - {}", run, self.difficulty.label())
+    }
+
+    //One tab separated line per run
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.mode.label(),
+            self.scenario.label(),
+            self.difficulty.label(),
+            self.score,
+            self.minerals,
+            self.waves,
+            self.date,
+            self.seed
+        )
+    }
+
+    fn from_line(line: &str) -> Option<LeaderboardEntry> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 8 {
+            return None;
+        }
+        Some(LeaderboardEntry {
+            mode: GameMode::from_label(fields[0])?,
+            scenario: Scenario::from_label(fields[1])?,
+            difficulty: Difficulty::from_label(fields[2])?,
+            score: fields[3].parse().ok()?,
+            minerals: fields[4].parse().ok()?,
+            waves: fields[5].parse().ok()?,
+            date: fields[6].to_string(),
+            seed: fields[7].parse().ok()?,
+        })
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Leaderboard {
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+impl Leaderboard {
+    //Best first, grouped by scenario and difficulty
+    pub fn tables(&self) -> Vec<(String, Vec<&LeaderboardEntry>)> {
+        let mut tables: Vec<(String, Vec<&LeaderboardEntry>)> = Vec::new();
+        for entry in self.entries.iter() {
+            let name = entry.table_name();
+            match tables
+                .iter_mut()
+                .find(|(table_name, _)| *table_name == name)
+            {
+                Some((_, rows)) => rows.push(entry),
+                None => tables.push((name, vec![entry])),
+            }
+        }
+        for (_, rows) in tables.iter_mut() {
+            rows.sort_by(|a, b| b.score.total_cmp(&a.score));
+        }
+        tables
+    }
+
+    fn add(&mut self, entry: LeaderboardEntry) {
+        self.entries.push(entry);
+        self.entries.sort_by(|a, b| b.score.total_cmp(&a.score));
+        let mut kept: Vec<(String, usize)> = Vec::new();
+        self.entries.retain(|entry| {
+            let name = entry.table_name();
+            match kept.iter_mut().find(|(table_name, _)| *table_name == name) {
+                Some((_, count)) => {
+                    *count += 1;
+                    *count <= ENTRIES_PER_TABLE
+                }
+                None => {
+                    kept.push((name, 1));
+                    true
+                }
+            }
+        });
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_leaderboard(mut leaderboard: ResMut<Leaderboard>) {
+    if let Ok(contents) = std::fs::read_to_string(LEADERBOARD_FILE) {
+        leaderboard.entries = contents
+            .lines()
+            .filter_map(LeaderboardEntry::from_line)
+            .collect();
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_leaderboard(leaderboard: &Leaderboard) {
+    let lines: Vec<String> = leaderboard.entries.iter().map(|e| e.to_line()).collect();
+    if let Err(err) = std::fs::write(LEADERBOARD_FILE, lines.join("\n")) {
+        warn!("Could not save the leaderboard: {}", err);
+    }
+}
+
+fn record_win(
+    score: Res<Score>,
+    map_seed: Res<MapSeed>,
+    game_mode: Res<GameMode>,
+    difficulty: Res<Difficulty>,
+    scenario: Res<Scenario>,
+    mut leaderboard: ResMut<Leaderboard>,
+) {
+    let run = (*game_mode, *scenario, *difficulty);
+    let total = score.total();
+    record_run(
+        run,
+        total,
+        score.waves_defeated,
+        &score,
+        &map_seed,
+        &mut leaderboard,
+    );
+}
+
+//A lost run gets nothing for finishing early
+fn record_loss(
+    score: Res<Score>,
+    map_seed: Res<MapSeed>,
+    game_mode: Res<GameMode>,
+    difficulty: Res<Difficulty>,
+    scenario: Res<Scenario>,
+    mut leaderboard: ResMut<Leaderboard>,
+) {
+    let run = (*game_mode, *scenario, *difficulty);
+    let total = score.total() - score.time_bonus();
+    record_run(
+        run,
+        total,
+        score.waves_defeated,
+        &score,
+        &map_seed,
+        &mut leaderboard,
+    );
+}
+
+//Endless runs score the seconds survived
+fn record_survival(
+    score: Res<Score>,
+    map_seed: Res<MapSeed>,
+    difficulty: Res<Difficulty>,
+    scenario: Res<Scenario>,
+    mut leaderboard: ResMut<Leaderboard>,
+) {
+    let run = (GameMode::Endless, *scenario, *difficulty);
+    let total = score.elapsed;
+    record_run(
+        run,
+        total,
+        score.wave_reached,
+        &score,
+        &map_seed,
+        &mut leaderboard,
+    );
+}
+
+fn record_run(
+    (mode, scenario, difficulty): (GameMode, Scenario, Difficulty),
+    total: f32,
+    waves: i32,
+    score: &Score,
+    map_seed: &MapSeed,
+    leaderboard: &mut Leaderboard,
+) {
+    leaderboard.add(LeaderboardEntry {
+        mode,
+        scenario,
+        difficulty,
+        score: total.round(),
+        minerals: score.minerals.round(),
+        waves,
+        date: today(),
+        seed: map_seed.seed,
+    });
+    #[cfg(not(target_arch = "wasm32"))]
+    save_leaderboard(leaderboard);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    date_from_unix_secs(secs)
+}
+
+//YYYY-MM-DD in UTC, without pulling in a date crate
+#[cfg(not(target_arch = "wasm32"))]
+fn date_from_unix_secs(secs: u64) -> String {
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+//The system clock isn't available in the browser
+#[cfg(target_arch = "wasm32")]
+fn today() -> String {
+    String::from("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(scenario: Scenario, difficulty: Difficulty, score: f32) -> LeaderboardEntry {
+        LeaderboardEntry {
+            mode: GameMode::Extraction,
+            scenario,
+            difficulty,
+            score,
+            minerals: 420.,
+            waves: 7,
+            date: String::from("2026-10-19"),
+            seed: 123456789,
+        }
+    }
+
+    #[test]
+    fn entries_survive_a_round_trip() {
+        let mut original = entry(Scenario::PirateBase, Difficulty::Hard, 1234.);
+        original.mode = GameMode::Endless;
+        let parsed = LeaderboardEntry::from_line(&original.to_line()).unwrap();
+        assert_eq!(parsed.mode, GameMode::Endless);
+        assert_eq!(parsed.scenario, Scenario::PirateBase);
+        assert_eq!(parsed.difficulty, Difficulty::Hard);
+        assert_eq!(parsed.score, 1234.);
+        assert_eq!(parsed.minerals, 420.);
+        assert_eq!(parsed.waves, 7);
+        assert_eq!(parsed.date, "2026-10-19");
+        assert_eq!(parsed.seed, 123456789);
+    }
+
+    #[test]
+    fn broken_lines_are_skipped() {
+        assert!(LeaderboardEntry::from_line("").is_none());
+        assert!(LeaderboardEntry::from_line("Endless\tNormal\t10").is_none());
+        let line = entry(Scenario::Convoy, Difficulty::Easy, 10.).to_line();
+        assert!(LeaderboardEntry::from_line(&line.replace("Easy", "Impossible")).is_none());
+        assert!(LeaderboardEntry::from_line(&line.replace("420", "lots")).is_none());
+    }
+
+    #[test]
+    fn every_table_keeps_its_best_runs() {
+        let mut leaderboard = Leaderboard::default();
+        for i in 0..ENTRIES_PER_TABLE + 5 {
+            leaderboard.add(entry(Scenario::Convoy, Difficulty::Normal, i as f32));
+        }
+        leaderboard.add(entry(Scenario::Convoy, Difficulty::Hard, 1.));
+        leaderboard.add(entry(Scenario::HoldTheLine, Difficulty::Normal, 1.));
+
+        let tables = leaderboard.tables();
+        assert_eq!(tables.len(), 3);
+        let (_, convoy) = tables
+            .iter()
+            .find(|(name, _)| name == "Convoy escort - Normal")
+            .unwrap();
+        assert_eq!(convoy.len(), ENTRIES_PER_TABLE);
+        assert_eq!(convoy[0].score, (ENTRIES_PER_TABLE + 4) as f32);
+        assert_eq!(convoy[ENTRIES_PER_TABLE - 1].score, 5.);
+        assert_eq!(leaderboard.entries.len(), ENTRIES_PER_TABLE + 2);
+    }
+
+    #[test]
+    fn endless_runs_share_a_table() {
+        let mut a = entry(Scenario::Convoy, Difficulty::Normal, 1.);
+        let mut b = entry(Scenario::PirateBase, Difficulty::Normal, 2.);
+        a.mode = GameMode::Endless;
+        b.mode = GameMode::Endless;
+        assert_eq!(a.table_name(), "Endless - Normal");
+        assert_eq!(a.table_name(), b.table_name());
+    }
+
+    #[test]
+    fn dates_are_counted_from_the_unix_epoch() {
+        assert_eq!(date_from_unix_secs(0), "1970-01-01");
+        assert_eq!(date_from_unix_secs(946598400), "1999-12-31");
+        assert_eq!(date_from_unix_secs(951782400), "2000-02-29");
+        assert_eq!(date_from_unix_secs(951868800), "2000-03-01");
+        assert_eq!(date_from_unix_secs(1709164800 + 86399), "2024-02-29");
+        assert_eq!(date_from_unix_secs(1792368000), "2026-10-19");
+    }
+}
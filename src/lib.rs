@@ -3,9 +3,18 @@
     windows_subsystem = "windows"
 )]
 use bevy::prelude::*;
+mod abilities;
+mod campaign;
+mod difficulty;
+mod diplomacy;
+mod leaderboard;
 mod materials;
 mod movement;
+mod objectives;
+mod research;
+mod scoring;
 mod selection;
+mod structures;
 mod ui;
 mod units;
 use bevy::asset::AssetMetaCheck;
@@ -13,9 +22,14 @@ use bevy::render::camera::ClearColorConfig;
 use bevy::render::view::visibility::RenderLayers;
 use bevy::window::{CursorGrabMode, PrimaryWindow, WindowMode};
 use bevy_rapier2d::prelude::*;
-use rand::Rng;
+use difficulty::Difficulty;
+use diplomacy::LocalPlayer;
+use materials::AsteroidBrain;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use scoring::Score;
 use selection::Team;
-use units::MotherUnit;
+use units::{EnemyBrain, MotherUnit};
 
 #[derive(Component)]
 pub struct MainCamera;
@@ -27,6 +41,8 @@ enum AppState {
     #[default]
     Menu,
     InGame,
+    //Between two campaign missions
+    FleetScreen,
 }
 
 #[derive(SubStates, Clone, PartialEq, Eq, Hash, Debug, Default)]
@@ -36,6 +52,40 @@ enum GamePhase {
     Playing,
     Lost,
     Won,
+    //The mothership fell in endless mode
+    Survived,
+}
+
+//Picked in the main menu, decides how a run can end
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum GameMode {
+    //Survive until the extraction point shows up and fly there
+    #[default]
+    Extraction,
+    //No extraction, waves and asteroids keep speeding up
+    Endless,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 2] = [GameMode::Extraction, GameMode::Endless];
+
+    pub fn from_label(label: &str) -> Option<GameMode> {
+        GameMode::ALL.into_iter().find(|m| m.label() == label)
+    }
+
+    pub fn next(&self) -> GameMode {
+        match self {
+            GameMode::Extraction => GameMode::Endless,
+            GameMode::Endless => GameMode::Extraction,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GameMode::Extraction => "Asteroid field",
+            GameMode::Endless => "Endless",
+        }
+    }
 }
 
 #[derive(Resource)]
@@ -55,25 +105,73 @@ impl Default for MapBoundaries {
 impl Plugin for StartupPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, build_world);
+        app.add_systems(OnEnter(AppState::InGame), (seed_map, reset_end_game_timer));
         app.add_systems(OnExit(AppState::InGame), despawn_everything);
         app.add_systems(OnExit(AppState::Menu), despawn_everything);
+        app.add_systems(OnExit(AppState::FleetScreen), despawn_everything);
         app.add_systems(Update, detect_lose.run_if(in_state(GamePhase::Playing)));
         app.add_systems(Update, (draw_rect_for_main_cam, keep_camera_in_bounderies));
         #[cfg(not(target_arch = "wasm32"))]
         app.add_systems(OnEnter(GamePhase::Playing), cursor_grab);
         #[cfg(not(target_arch = "wasm32"))]
         app.add_systems(OnExit(GamePhase::Playing), cursor_ungrab);
-        app.add_systems(Update, check_if_won.run_if(in_state(GamePhase::Playing)));
+        app.add_systems(
+            Update,
+            escalate_endless
+                .run_if(in_state(GamePhase::Playing).and_then(resource_equals(GameMode::Endless))),
+        );
         app.add_systems(
             Update,
             return_to_main_menu.run_if(in_state(AppState::InGame)),
         );
-        app.add_systems(Update, spawn_end_point);
+        app.add_systems(
+            Update,
+            spawn_end_point.run_if(resource_equals(GameMode::Extraction)),
+        );
         app.init_resource::<MapBoundaries>();
         app.init_resource::<EndGameTimer>();
+        app.init_resource::<MapSeed>();
+        app.init_resource::<GameMode>();
+        app.insert_resource(MapRng::new(0));
+    }
+}
+
+//Seed of the current run, a replay seed is used once by the next run
+#[derive(Resource, Default)]
+pub struct MapSeed {
+    pub seed: u64,
+    pub replay: Option<u64>,
+}
+
+//Drives asteroid fields, pirate waves and the map layout, so a seed gives the same map.
+//Every director draws from its own stream, so how the player plays can't shift the others.
+//Randomness that depends on the player, like asteroids breaking apart, stays off these streams.
+#[derive(Resource)]
+pub struct MapRng {
+    pub waves: StdRng,
+    pub asteroids: StdRng,
+    //Objectives and the extraction point
+    pub layout: StdRng,
+}
+
+impl MapRng {
+    pub fn new(seed: u64) -> MapRng {
+        MapRng {
+            waves: StdRng::seed_from_u64(seed),
+            asteroids: StdRng::seed_from_u64(seed.wrapping_add(1)),
+            layout: StdRng::seed_from_u64(seed.wrapping_add(2)),
+        }
     }
 }
 
+fn seed_map(mut map_seed: ResMut<MapSeed>, mut map_rng: ResMut<MapRng>) {
+    map_seed.seed = map_seed
+        .replay
+        .take()
+        .unwrap_or_else(|| rand::thread_rng().gen());
+    *map_rng = MapRng::new(map_seed.seed);
+}
+
 fn build_world(mut cmd: Commands, mut config_store: ResMut<GizmoConfigStore>) {
     cmd.spawn(Camera2dBundle {
         projection: OrthographicProjection {
@@ -152,6 +250,15 @@ pub fn run() {
         .add_plugins(units::UnitsPlugin)
         .add_plugins(movement::MovementPlugin)
         .add_plugins(materials::MaterialPlugin)
+        .add_plugins(diplomacy::DiplomacyPlugin)
+        .add_plugins(abilities::AbilitiesPlugin)
+        .add_plugins(structures::StructuresPlugin)
+        .add_plugins(research::ResearchPlugin)
+        .add_plugins(scoring::ScoringPlugin)
+        .add_plugins(leaderboard::LeaderboardPlugin)
+        .add_plugins(difficulty::DifficultyPlugin)
+        .add_plugins(objectives::ObjectivesPlugin)
+        .add_plugins(campaign::CampaignPlugin)
         .init_gizmo_group::<MiniMapGizmos>()
         .run();
 }
@@ -180,18 +287,23 @@ fn despawn_everything(
 //TODO: Add a tracker on the motherunit so we can move the camera there on lose!
 fn detect_lose(
     mother_ship: Query<&Team, With<MotherUnit>>,
+    local_player: Res<LocalPlayer>,
     mut game_phase: ResMut<NextState<GamePhase>>,
+    game_mode: Res<GameMode>,
 ) {
     let mut has_mother_ship = false;
     for team in mother_ship.iter() {
-        if team.0 == 0 {
+        if team.0 == local_player.0 {
             has_mother_ship = true;
             break;
         }
     }
 
     if !has_mother_ship {
-        game_phase.set(GamePhase::Lost);
+        match *game_mode {
+            GameMode::Extraction => game_phase.set(GamePhase::Lost),
+            GameMode::Endless => game_phase.set(GamePhase::Survived),
+        }
     }
 }
 
@@ -233,27 +345,6 @@ fn keep_camera_in_bounderies(
 #[derive(Component)]
 pub struct EndPoint;
 
-fn check_if_won(
-    mother_unit: Query<&Transform, With<MotherUnit>>,
-    end_points: Query<&Transform, (With<EndPoint>, Without<MotherUnit>)>,
-    mut main_camera: Query<
-        &mut Transform,
-        (With<MainCamera>, Without<MotherUnit>, Without<EndPoint>),
-    >,
-    mut game_phase: ResMut<NextState<GamePhase>>,
-) {
-    for mother_tr in mother_unit.iter() {
-        for end_point_tr in end_points.iter() {
-            if (mother_tr.translation - end_point_tr.translation).length() < 50.0 {
-                for mut cam_tr in main_camera.iter_mut() {
-                    cam_tr.translation = mother_tr.translation;
-                }
-                game_phase.set(GamePhase::Won);
-            }
-        }
-    }
-}
-
 #[derive(Resource)]
 pub struct EndGameTimer(Timer);
 
@@ -263,10 +354,32 @@ impl Default for EndGameTimer {
     }
 }
 
+//Pirate waves come quicker every wave and asteroids quicker every minute
+fn escalate_endless(
+    mut enemy_brain: ResMut<EnemyBrain>,
+    mut asteroid_brain: ResMut<AsteroidBrain>,
+    difficulty: Res<Difficulty>,
+    score: Res<Score>,
+) {
+    let wave_interval =
+        (difficulty.wave_interval() * 0.95_f32.powi(enemy_brain.current_wave)).max(5.);
+    enemy_brain
+        .time_between_wave
+        .set_duration(std::time::Duration::from_secs_f32(wave_interval));
+    let asteroid_interval = (10. * 0.85_f32.powf(score.elapsed / 60.)).max(2.);
+    asteroid_brain
+        .time_between_wave
+        .set_duration(std::time::Duration::from_secs_f32(asteroid_interval));
+}
+
+fn reset_end_game_timer(mut end_game_timer: ResMut<EndGameTimer>, difficulty: Res<Difficulty>) {
+    end_game_timer.0 = Timer::from_seconds(difficulty.extraction_time(), TimerMode::Once);
+}
+
 fn spawn_end_point(
     mut cmd: Commands,
     asset_server: Res<AssetServer>,
-    map_boundaries: Res<MapBoundaries>,
+    (map_boundaries, mut map_rng): (Res<MapBoundaries>, ResMut<MapRng>),
     end_points: Query<Entity, With<EndPoint>>,
     time: Res<Time>,
     mut end_game_timer: ResMut<EndGameTimer>,
@@ -281,7 +394,7 @@ fn spawn_end_point(
     end_game_timer.0.tick(time.delta());
     if end_game_timer.0.finished() {
         if end_point_count == 0 {
-            let mut rng = rand::thread_rng();
+            let rng = &mut map_rng.layout;
             for mother_tr in mother_unit_q.iter() {
                 let mut spawn_pos = mother_tr.translation();
                 let mut repeat_counter = 0;
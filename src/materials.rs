@@ -1,23 +1,163 @@
 use std::f32::consts::PI;
 
+use crate::difficulty::Difficulty;
 use crate::movement::MoveForward;
 use crate::selection::Selectable;
-use crate::units::Health;
+use crate::selection::Team;
+use crate::structures::MiningOutpost;
+use crate::units::{
+    spawn_explosion_visual, DamageEvent, DamageType, Health, UnitCommand, UnitCommandList,
+    UnitDestroyed,
+};
 use crate::AppState;
+use crate::GamePhase;
 use crate::MapBoundaries;
+use crate::MapRng;
 use bevy::prelude::*;
 use bevy::render::view::visibility::RenderLayers;
 use bevy_rapier2d::prelude::*;
 use rand::Rng;
 pub struct MaterialPlugin;
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ResourceKind {
+    Ore,
+    Ice,
+    Crystal,
+}
+
+impl ResourceKind {
+    pub const ALL: [ResourceKind; 3] =
+        [ResourceKind::Ore, ResourceKind::Ice, ResourceKind::Crystal];
+
+    pub fn tint(&self) -> Color {
+        match self {
+            ResourceKind::Ore => Color::srgba(0.9, 0.75, 0.6, 1.),
+            ResourceKind::Ice => Color::srgba(0.6, 0.85, 1., 1.),
+            ResourceKind::Crystal => Color::srgba(0.9, 0.45, 1., 1.),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ResourceKind::Ore => "Ore",
+            ResourceKind::Ice => "Ice",
+            ResourceKind::Crystal => "Crystal",
+        }
+    }
+}
+
+//Price of something, one amount per resource kind
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct ResourceCost {
+    pub ore: f32,
+    pub ice: f32,
+    pub crystal: f32,
+}
+
+impl ResourceCost {
+    pub const fn new(ore: f32, ice: f32, crystal: f32) -> ResourceCost {
+        ResourceCost { ore, ice, crystal }
+    }
+
+    pub fn get(&self, kind: ResourceKind) -> f32 {
+        match kind {
+            ResourceKind::Ore => self.ore,
+            ResourceKind::Ice => self.ice,
+            ResourceKind::Crystal => self.crystal,
+        }
+    }
+
+    pub fn total(&self) -> f32 {
+        self.ore + self.ice + self.crystal
+    }
+
+    //Short text like "40 ore 20 ice", kinds that cost nothing are left out
+    pub fn label(&self) -> String {
+        let mut parts = Vec::new();
+        for kind in ResourceKind::ALL {
+            if self.get(kind) > 0. {
+                parts.push(format!(
+                    "{} {}",
+                    self.get(kind),
+                    kind.label().to_lowercase()
+                ));
+            }
+        }
+        parts.join(" ")
+    }
+
+    //Compact version for unit cards, like "50o30c"
+    pub fn short_label(&self) -> String {
+        let mut label = String::new();
+        for kind in ResourceKind::ALL {
+            if self.get(kind) > 0. {
+                label += &format!(
+                    "{}{}",
+                    self.get(kind),
+                    kind.label().to_lowercase().remove(0)
+                );
+            }
+        }
+        label
+    }
+}
+
 #[derive(Resource)]
-pub struct MineralResources {
-    pub mineral: f32,
+pub struct ResourceLedger {
+    pub ore: f32,
+    pub ice: f32,
+    pub crystal: f32,
+    //Everything mined and brought back this run, spending doesn't lower it
+    pub extracted: f32,
+}
+impl Default for ResourceLedger {
+    fn default() -> ResourceLedger {
+        ResourceLedger {
+            ore: 50.0,
+            ice: 0.0,
+            crystal: 0.0,
+            extracted: 0.0,
+        }
+    }
 }
-impl Default for MineralResources {
-    fn default() -> MineralResources {
-        MineralResources { mineral: 50.0 }
+
+impl ResourceLedger {
+    pub fn get(&self, kind: ResourceKind) -> f32 {
+        match kind {
+            ResourceKind::Ore => self.ore,
+            ResourceKind::Ice => self.ice,
+            ResourceKind::Crystal => self.crystal,
+        }
+    }
+
+    pub fn add(&mut self, kind: ResourceKind, amount: f32) {
+        match kind {
+            ResourceKind::Ore => self.ore += amount,
+            ResourceKind::Ice => self.ice += amount,
+            ResourceKind::Crystal => self.crystal += amount,
+        }
+    }
+
+    //Mined cargo dropped off at a depot, counts towards the score
+    pub fn deposit(&mut self, kind: ResourceKind, amount: f32) {
+        self.add(kind, amount);
+        self.extracted += amount;
+    }
+
+    pub fn can_afford(&self, cost: &ResourceCost) -> bool {
+        self.ore >= cost.ore && self.ice >= cost.ice && self.crystal >= cost.crystal
+    }
+
+    //Pays the cost if every kind can be afforded, returns whether it was paid
+    pub fn spend(&mut self, cost: &ResourceCost) -> bool {
+        if !self.can_afford(cost) {
+            return false;
+        }
+        self.ore -= cost.ore;
+        self.ice -= cost.ice;
+        self.crystal -= cost.crystal;
+        true
     }
 }
 
@@ -27,9 +167,17 @@ impl Plugin for MaterialPlugin {
             OnEnter(AppState::InGame),
             (spawn_asetroids, reset_mastermind, reset_materials),
         );
-        app.add_systems(PostUpdate, delete_asteroids);
+        app.add_systems(
+            PostUpdate,
+            (delete_asteroids, spawn_wrecks, release_mining_slots),
+        );
+        app.add_systems(
+            Update,
+            (fracture_asteroids, asteroid_impacts, deflect_asteroids)
+                .run_if(in_state(GamePhase::Playing)),
+        );
         app.add_systems(Update, asteroid_mastermind);
-        app.init_resource::<MineralResources>();
+        app.init_resource::<ResourceLedger>();
         app.init_resource::<AsteroidBrain>();
     }
 }
@@ -37,6 +185,123 @@ impl Plugin for MaterialPlugin {
 #[derive(Component)]
 pub struct Mineable {
     pub amount: f32,
+    pub kind: ResourceKind,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AsteroidSize {
+    Large,
+    Medium,
+    Small,
+}
+
+impl AsteroidSize {
+    pub fn half_size(&self) -> f32 {
+        match self {
+            AsteroidSize::Large => 50.,
+            AsteroidSize::Medium => 30.,
+            AsteroidSize::Small => 15.,
+        }
+    }
+
+    pub fn max_health(&self) -> f32 {
+        match self {
+            AsteroidSize::Large => 100.,
+            AsteroidSize::Medium => 50.,
+            AsteroidSize::Small => 25.,
+        }
+    }
+
+    pub fn mining_slots(&self) -> usize {
+        match self {
+            AsteroidSize::Large => 4,
+            AsteroidSize::Medium => 3,
+            AsteroidSize::Small => 2,
+        }
+    }
+
+    //What the asteroid breaks into, and how many pieces
+    pub fn fragments(&self) -> Option<(AsteroidSize, usize)> {
+        match self {
+            AsteroidSize::Large => Some((AsteroidSize::Medium, 3)),
+            AsteroidSize::Medium => Some((AsteroidSize::Small, 2)),
+            AsteroidSize::Small => None,
+        }
+    }
+}
+
+//Spots around an asteroid's hull where miners can work, one miner per spot
+#[derive(Component)]
+pub struct MiningSlots {
+    pub occupants: Vec<Option<Entity>>,
+    pub radius: f32,
+}
+
+impl MiningSlots {
+    pub fn new(count: usize, radius: f32) -> MiningSlots {
+        MiningSlots {
+            occupants: vec![None; count],
+            radius,
+        }
+    }
+
+    pub fn offset(&self, slot: usize) -> Vec3 {
+        let angle = 2.0 * PI * slot as f32 / self.occupants.len() as f32;
+        (Vec2::from_angle(angle) * self.radius).extend(0.)
+    }
+
+    pub fn slot_of(&self, miner: Entity) -> Option<usize> {
+        self.occupants.iter().position(|o| *o == Some(miner))
+    }
+
+    //Slot the miner already holds, or the first free one
+    pub fn claim(&mut self, miner: Entity) -> Option<usize> {
+        if let Some(slot) = self.slot_of(miner) {
+            return Some(slot);
+        }
+        let slot = self.occupants.iter().position(|o| o.is_none())?;
+        self.occupants[slot] = Some(miner);
+        Some(slot)
+    }
+
+    pub fn used(&self) -> usize {
+        self.occupants.iter().filter(|o| o.is_some()).count()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.used() >= self.occupants.len()
+    }
+}
+
+//Miners and outposts that stopped mining here give their slot up
+fn release_mining_slots(
+    mut slots_q: Query<(Entity, &mut MiningSlots)>,
+    command_lists: Query<&UnitCommandList>,
+    outposts: Query<&MiningOutpost>,
+) {
+    for (mineable_e, mut slots) in slots_q.iter_mut() {
+        for occupant in slots.occupants.iter_mut() {
+            if let Some(miner) = *occupant {
+                let still_mining = command_lists.get(miner).is_ok_and(|command_list| {
+                    matches!(
+                        command_list.commands.first(),
+                        Some(UnitCommand::MineEntity(target)) if *target == mineable_e
+                    )
+                }) || outposts
+                    .get(miner)
+                    .is_ok_and(|outpost| outpost.target == Some(mineable_e));
+                if !still_mining {
+                    *occupant = None;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Asteroid {
+    pub size: AsteroidSize,
+    pub initial_amount: f32,
 }
 
 #[derive(Resource)]
@@ -55,8 +320,21 @@ fn reset_mastermind(mut asteroid_brain: ResMut<AsteroidBrain>) {
     *asteroid_brain = AsteroidBrain::default();
 }
 
-fn reset_materials(mut materials: ResMut<MineralResources>) {
-    *materials = MineralResources::default();
+fn reset_materials(mut materials: ResMut<ResourceLedger>, difficulty: Res<Difficulty>) {
+    *materials = ResourceLedger::default();
+    materials.ore = difficulty.starting_ore();
+}
+
+//Most asteroids are ore, crystals are rare and small
+fn random_resource_kind(rng: &mut impl Rng) -> (ResourceKind, f32, AsteroidSize) {
+    let roll = rng.gen_range(0.0..1.0);
+    if roll < 0.6 {
+        (ResourceKind::Ore, 250., AsteroidSize::Large)
+    } else if roll < 0.9 {
+        (ResourceKind::Ice, 200., AsteroidSize::Large)
+    } else {
+        (ResourceKind::Crystal, 120., AsteroidSize::Medium)
+    }
 }
 
 fn asteroid_mastermind(
@@ -65,11 +343,12 @@ fn asteroid_mastermind(
     time: Res<Time>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    mut map_rng: ResMut<MapRng>,
 ) {
     enemy_brain.time_between_wave.tick(time.delta());
     if enemy_brain.time_between_wave.finished() {
         enemy_brain.time_between_wave.reset();
-        let mut rng = rand::thread_rng();
+        let rng = &mut map_rng.asteroids;
 
         let spawn_side = rng.gen_range(0..4);
         let mut spawn_pos = Vec3::ZERO;
@@ -109,36 +388,53 @@ fn asteroid_mastermind(
             }
             _ => {}
         }
-        spawn_asteroid(&mut commands, &asset_server, spawn_pos, start_rotation);
+        let (kind, amount, size) = random_resource_kind(rng);
+        let mut transform = Transform::from_translation(spawn_pos);
+        transform.rotation = Quat::from_rotation_z(start_rotation);
+        spawn_asteroid(
+            &mut commands,
+            &asset_server,
+            transform,
+            40.,
+            size,
+            Mineable { amount, kind },
+        );
     }
 }
-fn spawn_asteroid(
+pub fn spawn_asteroid(
     cmd: &mut Commands,
     asset_server: &Res<AssetServer>,
-    spawn_pos: Vec3,
-    z_rotation: f32,
-) {
-    let mut transform = Transform::from_translation(spawn_pos);
-    transform.rotation = Quat::from_rotation_z(z_rotation);
+    transform: Transform,
+    speed: f32,
+    size: AsteroidSize,
+    mineable: Mineable,
+) -> Entity {
+    let half_size = size.half_size();
+    let tint = mineable.kind.tint();
     cmd.spawn(SpatialBundle {
         transform,
         ..Default::default()
     })
-    .insert(Collider::cuboid(50.0, 50.0))
+    .insert(Collider::cuboid(half_size, half_size))
     .insert(Sensor)
     .insert(Selectable)
     .insert(Health {
-        current: 100.,
-        max_health: 100.,
+        current: size.max_health(),
+        max_health: size.max_health(),
     })
-    .insert(MoveForward { speed: 40. })
-    .insert(Mineable { amount: 250. })
+    .insert(MoveForward { speed })
+    .insert(Asteroid {
+        size,
+        initial_amount: mineable.amount,
+    })
+    .insert(MiningSlots::new(size.mining_slots(), half_size + 45.))
+    .insert(mineable)
     .with_children(|parent| {
         parent.spawn(SpriteBundle {
             texture: asset_server.load("units/meteor_squareDetailedLarge.png"),
             sprite: Sprite {
-                color: Color::srgba(1., 1., 1., 1.),
-                custom_size: Some(Vec2::new(150., 150.)),
+                color: tint,
+                custom_size: Some(Vec2::splat(half_size * 3.)),
                 ..default()
             },
             ..Default::default()
@@ -147,53 +443,265 @@ fn spawn_asteroid(
             .spawn(SpriteBundle {
                 texture: asset_server.load("units/meteor_squareDetailedLarge.png"),
                 sprite: Sprite {
-                    color: Color::srgba(1., 1., 1., 1.),
-                    custom_size: Some(Vec2::new(150., 150.)),
+                    color: tint,
+                    custom_size: Some(Vec2::splat(half_size * 3.)),
                     ..default()
                 },
                 ..Default::default()
             })
             .insert(RenderLayers::layer(1));
-    });
+    })
+    .id()
 }
 
-fn spawn_asetroids(mut cmd: Commands, asset_server: Res<AssetServer>) {
-    for i in 0..1 {
-        cmd.spawn(SpatialBundle {
-            transform: Transform::from_translation(Vec3::new(i as f32 * 100., 300. + 100., -5.0)),
-            ..Default::default()
-        })
-        .insert(Collider::cuboid(50.0, 50.0))
-        .insert(Sensor)
-        .insert(Selectable)
-        .insert(Health {
-            current: 100.,
-            max_health: 100.,
-        })
-        .insert(MoveForward { speed: 1. })
-        .insert(Mineable { amount: 250. })
-        .with_children(|parent| {
-            parent.spawn(SpriteBundle {
-                texture: asset_server.load("units/meteor_squareDetailedLarge.png"),
-                sprite: Sprite {
-                    color: Color::srgba(1., 1., 1., 1.),
-                    custom_size: Some(Vec2::new(150., 150.)),
-                    ..default()
-                },
+//Big asteroids break apart when shot to pieces or mined down to this fraction of what they held
+const FRACTURE_THRESHOLD: f32 = 0.5;
+
+fn fracture_asteroids(
+    mut cmd: Commands,
+    asteroids: Query<(
+        Entity,
+        &Asteroid,
+        &Mineable,
+        &Health,
+        &Transform,
+        &MoveForward,
+    )>,
+    mut command_lists: Query<(&mut UnitCommandList, &Transform)>,
+    mut ev_destroyed: EventWriter<UnitDestroyed>,
+    asset_server: Res<AssetServer>,
+) {
+    //When an asteroid breaks is up to the player, so this stays off the map streams
+    let mut rng = rand::thread_rng();
+    for (e, asteroid, mineable, hp, tr, move_forward) in asteroids.iter() {
+        let shot_apart = hp.current <= 0.;
+        let mined_down = mineable.amount <= asteroid.initial_amount * FRACTURE_THRESHOLD;
+        if !shot_apart && !mined_down {
+            continue;
+        }
+        let fragments = asteroid.size.fragments();
+        if fragments.is_none() && !shot_apart {
+            continue;
+        }
+
+        let mut fragment_entities = Vec::new();
+        if let Some((fragment_size, count)) = fragments {
+            let amount_per_fragment = (mineable.amount / count as f32).floor();
+            if amount_per_fragment >= 1. {
+                let heading = tr.right().truncate().to_angle();
+                for _ in 0..count {
+                    //Fragments keep flying roughly the same way, spread out a bit
+                    let rotation = heading + rng.gen_range(-0.6..0.6);
+                    let offset = Vec2::from_angle(rng.gen_range(0.0..(2.0 * PI)))
+                        * asteroid.size.half_size()
+                        * 0.5;
+                    let mut fragment_tr =
+                        Transform::from_translation(tr.translation + offset.extend(0.));
+                    fragment_tr.rotation = Quat::from_rotation_z(rotation);
+                    let fragment_pos = fragment_tr.translation;
+                    let fragment_e = spawn_asteroid(
+                        &mut cmd,
+                        &asset_server,
+                        fragment_tr,
+                        move_forward.speed * rng.gen_range(0.8..1.4),
+                        fragment_size,
+                        Mineable {
+                            amount: amount_per_fragment,
+                            kind: mineable.kind,
+                        },
+                    );
+                    fragment_entities.push((fragment_e, fragment_pos));
+                }
+            }
+        }
+        spawn_explosion_visual(
+            &mut cmd,
+            &asset_server,
+            tr.translation,
+            asteroid.size.half_size() * 2.,
+        );
+
+        //Miners keep working on whichever piece is closest to them
+        for (mut command_list, miner_tr) in command_lists.iter_mut() {
+            let mut closest_fragment = None;
+            let mut closest_dist = f32::MAX;
+            for (fragment_e, fragment_pos) in fragment_entities.iter() {
+                let dist = (*fragment_pos - miner_tr.translation).length();
+                if dist < closest_dist {
+                    closest_dist = dist;
+                    closest_fragment = Some(*fragment_e);
+                }
+            }
+            let Some(closest_fragment) = closest_fragment else {
+                continue;
+            };
+            for c in command_list.commands.iter_mut() {
+                match c {
+                    UnitCommand::MineEntity(target) if *target == e => {
+                        *target = closest_fragment;
+                    }
+                    UnitCommand::ReturnCargoToUnit(_, Some(last_mineable))
+                        if *last_mineable == e =>
+                    {
+                        *last_mineable = closest_fragment;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        ev_destroyed.send(UnitDestroyed {
+            entity: e,
+            team: None,
+            killer: None,
+            position: tr.translation,
+            archetype: None,
+            drone: false,
+        });
+        cmd.entity(e).despawn_recursive();
+    }
+}
+
+//Damage per second a large asteroid at normal drift speed deals to a ship it runs into
+const IMPACT_DAMAGE: f32 = 30.;
+const DRIFT_SPEED: f32 = 40.;
+
+fn asteroid_impacts(
+    time: Res<Time>,
+    asteroids: Query<(Entity, &Asteroid, &Transform, &MoveForward)>,
+    ships: Query<(), (With<Team>, With<Health>)>,
+    rapier_context: Res<RapierContext>,
+    mut ev_damage: EventWriter<DamageEvent>,
+) {
+    for (asteroid_e, asteroid, tr, move_forward) in asteroids.iter() {
+        let half_size = asteroid.size.half_size();
+        let dmg_amount = IMPACT_DAMAGE
+            * (move_forward.speed / DRIFT_SPEED)
+            * (half_size / AsteroidSize::Large.half_size())
+            * time.delta_seconds();
+        let shape = Collider::ball(half_size);
+        rapier_context.intersections_with_shape(
+            tr.translation.truncate(),
+            0.,
+            &shape,
+            QueryFilter::default(),
+            |hit| {
+                if hit != asteroid_e && ships.get(hit).is_ok() {
+                    ev_damage.send(DamageEvent {
+                        target: hit,
+                        dmg_amount,
+                        damager: None,
+                        dmg_type: DamageType::Kinetic,
+                    });
+                }
+                true
+            },
+        );
+    }
+}
+
+//Asteroids that touch bounce their heading off each other
+fn deflect_asteroids(mut asteroids: Query<(Entity, &Asteroid, &mut Transform)>) {
+    let all_asteroids: Vec<(Entity, Vec3, f32)> = asteroids
+        .iter()
+        .map(|(e, asteroid, tr)| (e, tr.translation, asteroid.size.half_size()))
+        .collect();
+    for (e, asteroid, mut tr) in asteroids.iter_mut() {
+        let mut heading = tr.right().truncate();
+        let mut deflected = false;
+        for (other_e, other_pos, other_half_size) in all_asteroids.iter() {
+            if *other_e == e {
+                continue;
+            }
+            let normal = (*other_pos - tr.translation).truncate();
+            if normal.length() > asteroid.size.half_size() + other_half_size {
+                continue;
+            }
+            let normal = normal.normalize_or_zero();
+            //Only bounce while still moving into the other asteroid
+            if heading.dot(normal) > 0. {
+                heading -= 2. * heading.dot(normal) * normal;
+                deflected = true;
+            }
+        }
+        if deflected {
+            tr.rotation = Quat::from_rotation_z(heading.to_angle());
+        }
+    }
+}
+
+//Fraction of a destroyed unit's cost that can be salvaged from its wreck
+const SALVAGE_FRACTION: f32 = 0.5;
+
+#[derive(Component)]
+pub struct Wreck;
+
+fn spawn_wrecks(
+    mut cmd: Commands,
+    mut ev_destroyed: EventReader<UnitDestroyed>,
+    asset_server: Res<AssetServer>,
+) {
+    for destroyed in ev_destroyed.read() {
+        if destroyed.drone {
+            continue;
+        }
+        if let Some(archetype) = destroyed.archetype {
+            let salvage = (archetype.cost() * SALVAGE_FRACTION).floor();
+            if salvage < 1.0 {
+                continue;
+            }
+            cmd.spawn(SpatialBundle {
+                transform: Transform::from_translation(destroyed.position.truncate().extend(-5.)),
                 ..Default::default()
-            });
-            parent
-                .spawn(SpriteBundle {
-                    texture: asset_server.load("units/meteor_squareDetailedLarge.png"),
+            })
+            .insert(Collider::cuboid(25.0, 25.0))
+            .insert(Sensor)
+            .insert(Selectable)
+            .insert(Wreck)
+            .insert(MiningSlots::new(2, 70.))
+            .insert(Mineable {
+                amount: salvage,
+                kind: ResourceKind::Ore,
+            })
+            .with_children(|parent| {
+                parent.spawn(SpriteBundle {
+                    texture: asset_server.load("units/meteor_small.png"),
                     sprite: Sprite {
-                        color: Color::srgba(1., 1., 1., 1.),
-                        custom_size: Some(Vec2::new(150., 150.)),
+                        color: Color::srgba(0.5, 0.5, 0.5, 1.),
+                        custom_size: Some(Vec2::new(50., 50.)),
                         ..default()
                     },
                     ..Default::default()
-                })
-                .insert(RenderLayers::layer(1));
-        });
+                });
+                parent
+                    .spawn(SpriteBundle {
+                        texture: asset_server.load("units/meteor_small.png"),
+                        sprite: Sprite {
+                            color: Color::srgba(0.5, 0.5, 0.5, 1.),
+                            custom_size: Some(Vec2::new(50., 50.)),
+                            ..default()
+                        },
+                        ..Default::default()
+                    })
+                    .insert(RenderLayers::layer(1));
+            });
+        }
+    }
+}
+
+fn spawn_asetroids(mut cmd: Commands, asset_server: Res<AssetServer>) {
+    for i in 0..1 {
+        spawn_asteroid(
+            &mut cmd,
+            &asset_server,
+            Transform::from_translation(Vec3::new(i as f32 * 100., 300. + 100., -5.0)),
+            1.,
+            AsteroidSize::Large,
+            Mineable {
+                amount: 250.,
+                kind: ResourceKind::Ore,
+            },
+        );
     }
 }
 
@@ -22,11 +22,18 @@ pub struct MoveForward {
     pub speed: f32,
 }
 
+//Velocity measured from frame to frame, so shooters can lead their targets
+#[derive(Component, Default)]
+pub struct MotionTracker {
+    pub last_pos: Option<Vec3>,
+    pub velocity: Vec3,
+}
+
 pub struct MovementPlugin;
 
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(PostUpdate, face_towards_movement);
+        app.add_systems(PostUpdate, (face_towards_movement, track_motion));
         app.add_systems(
             Update,
             (avoid_each_other, camera_mover, move_forward).run_if(in_state(GamePhase::Playing)),
@@ -89,6 +96,18 @@ fn face_towards_movement(
     }
 }
 
+fn track_motion(time: Res<Time>, mut trackers: Query<(&Transform, &mut MotionTracker)>) {
+    if time.delta_seconds() <= 0. {
+        return;
+    }
+    for (tr, mut tracker) in trackers.iter_mut() {
+        if let Some(last_pos) = tracker.last_pos {
+            tracker.velocity = (tr.translation - last_pos) / time.delta_seconds();
+        }
+        tracker.last_pos = Some(tr.translation);
+    }
+}
+
 #[derive(Resource, Default)]
 struct LastCursorPos(Vec2);
 
@@ -0,0 +1,550 @@
+use crate::diplomacy::{Diplomacy, LocalPlayer};
+use crate::materials::ResourceLedger;
+use crate::movement::FaceMovementDirection;
+use crate::scoring::ExtractionQuota;
+use crate::selection::Team;
+use crate::structures::{StaticDefense, Structure};
+use crate::units::{
+    Armor, AttackComponent, DamageType, Health, HealthBar, MotherUnit, ProjectileKind, Veterancy,
+};
+use crate::AppState;
+use crate::EndPoint;
+use crate::GameMode;
+use crate::GamePhase;
+use crate::MainCamera;
+use crate::MapBoundaries;
+use crate::MapRng;
+use crate::MiniMapGizmos;
+use bevy::prelude::*;
+use bevy::render::view::visibility::RenderLayers;
+use bevy_rapier2d::prelude::*;
+use rand::Rng;
+
+pub struct ObjectivesPlugin;
+
+impl Plugin for ObjectivesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(AppState::InGame),
+            setup_objectives.after(crate::seed_map),
+        );
+        app.add_systems(
+            Update,
+            (
+                move_convoys,
+                update_objectives,
+                resolve_objectives,
+                draw_objective_markers,
+            )
+                .chain()
+                .run_if(in_state(GamePhase::Playing)),
+        );
+        app.init_resource::<Objectives>();
+        app.init_resource::<Scenario>();
+    }
+}
+
+//Picked in the main menu for extraction runs, decides the objectives on top of extracting
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Scenario {
+    #[default]
+    AsteroidField,
+    Convoy,
+    PirateBase,
+    HoldTheLine,
+}
+
+impl Scenario {
+    pub const ALL: [Scenario; 4] = [
+        Scenario::AsteroidField,
+        Scenario::Convoy,
+        Scenario::PirateBase,
+        Scenario::HoldTheLine,
+    ];
+
+    pub fn from_label(label: &str) -> Option<Scenario> {
+        Scenario::ALL.into_iter().find(|s| s.label() == label)
+    }
+
+    pub fn next(&self) -> Scenario {
+        match self {
+            Scenario::AsteroidField => Scenario::Convoy,
+            Scenario::Convoy => Scenario::PirateBase,
+            Scenario::PirateBase => Scenario::HoldTheLine,
+            Scenario::HoldTheLine => Scenario::AsteroidField,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Scenario::AsteroidField => "Asteroid field",
+            Scenario::Convoy => "Convoy escort",
+            Scenario::PirateBase => "Pirate base",
+            Scenario::HoldTheLine => "Hold the line",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ObjectiveStatus {
+    Active,
+    Completed,
+    Failed,
+}
+
+pub enum ObjectiveKind {
+    //Mothership within radius of the point, None until the point is known
+    ReachPoint {
+        target: Option<Vec3>,
+        radius: f32,
+    },
+    //The convoy has to arrive in one piece
+    Escort {
+        convoy: Entity,
+        destination: Vec3,
+    },
+    DestroyBase {
+        base: Entity,
+    },
+    //Own ships in the area and no pirates for the whole timer
+    HoldArea {
+        center: Vec3,
+        radius: f32,
+        held: Timer,
+    },
+    MineMinerals {
+        amount: f32,
+    },
+}
+
+pub struct Objective {
+    pub name: &'static str,
+    pub kind: ObjectiveKind,
+    pub status: ObjectiveStatus,
+    pub progress: String,
+}
+
+impl Objective {
+    pub fn new(name: &'static str, kind: ObjectiveKind) -> Objective {
+        Objective {
+            name,
+            kind,
+            status: ObjectiveStatus::Active,
+            progress: String::new(),
+        }
+    }
+}
+
+//Every objective has to be completed at once to win, a failed one loses the run
+#[derive(Resource, Default)]
+pub struct Objectives(pub Vec<Objective>);
+
+#[derive(Component)]
+pub struct ConvoyShip {
+    pub destination: Vec3,
+    pub speed: f32,
+}
+
+#[derive(Component)]
+pub struct PirateBase;
+
+//A random spot on the map at least min_distance away from the start
+fn random_map_pos(rng: &mut impl Rng, boundaries: &MapBoundaries, min_distance: f32) -> Vec3 {
+    let mut pos = Vec3::ZERO;
+    for _ in 0..20 {
+        pos = Vec3::new(
+            rng.gen_range((boundaries.x_boundaries.x + 200.)..(boundaries.x_boundaries.y - 200.)),
+            rng.gen_range((boundaries.y_boundaries.x + 200.)..(boundaries.y_boundaries.y - 200.)),
+            0.,
+        );
+        if pos.length() >= min_distance {
+            break;
+        }
+    }
+    pos
+}
+
+fn setup_objectives(
+    mut cmd: Commands,
+    mut objectives: ResMut<Objectives>,
+    scenario: Res<Scenario>,
+    game_mode: Res<GameMode>,
+    quota: Res<ExtractionQuota>,
+    (boundaries, mut map_rng): (Res<MapBoundaries>, ResMut<MapRng>),
+    asset_server: Res<AssetServer>,
+) {
+    objectives.0.clear();
+    if *game_mode == GameMode::Endless {
+        return;
+    }
+    let rng = &mut map_rng.layout;
+    match *scenario {
+        Scenario::AsteroidField => {}
+        Scenario::Convoy => {
+            let destination = random_map_pos(rng, &boundaries, 1500.);
+            let convoy = spawn_convoy(
+                &mut cmd,
+                Vec3::new(-150., -100., 0.),
+                destination,
+                &asset_server,
+            );
+            objectives.0.push(Objective::new(
+                "Escort the convoy",
+                ObjectiveKind::Escort {
+                    convoy,
+                    destination,
+                },
+            ));
+        }
+        Scenario::PirateBase => {
+            let base = spawn_pirate_base(
+                &mut cmd,
+                random_map_pos(rng, &boundaries, 1200.),
+                &asset_server,
+            );
+            objectives.0.push(Objective::new(
+                "Destroy the pirate base",
+                ObjectiveKind::DestroyBase { base },
+            ));
+        }
+        Scenario::HoldTheLine => {
+            objectives.0.push(Objective::new(
+                "Hold the marked area",
+                ObjectiveKind::HoldArea {
+                    center: random_map_pos(rng, &boundaries, 600.),
+                    radius: 250.,
+                    held: Timer::from_seconds(60., TimerMode::Once),
+                },
+            ));
+        }
+    }
+    if quota.enabled {
+        objectives.0.push(Objective::new(
+            "Fill the quota",
+            ObjectiveKind::MineMinerals {
+                amount: quota.amount,
+            },
+        ));
+    }
+    objectives.0.push(Objective::new(
+        "Extract the mothership",
+        ObjectiveKind::ReachPoint {
+            target: None,
+            radius: 50.,
+        },
+    ));
+}
+
+fn spawn_convoy(
+    cmd: &mut Commands,
+    spawn_pos: Vec3,
+    destination: Vec3,
+    asset_server: &Res<AssetServer>,
+) -> Entity {
+    cmd.spawn(SpatialBundle {
+        transform: Transform::from_translation(spawn_pos),
+        ..Default::default()
+    })
+    .insert(Collider::cuboid(30.0, 30.0))
+    .insert(Sensor)
+    .insert(Health {
+        current: 400.,
+        max_health: 400.,
+    })
+    .insert(Armor {
+        kinetic: 0.3,
+        energy: 0.1,
+        explosive: 0.,
+    })
+    .insert(Team(0))
+    .insert(ConvoyShip {
+        destination,
+        speed: 35.,
+    })
+    .with_children(|parent| {
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("units/station_A.png"),
+                sprite: Sprite {
+                    color: Color::srgb(0.6, 0.9, 1.),
+                    custom_size: Some(Vec2::new(70., 70.)),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(FaceMovementDirection {
+                face_to_pos: destination,
+            });
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("healthbar.png"),
+                transform: Transform::from_translation(Vec3::new(0., -40., 0.)),
+                sprite: Sprite {
+                    color: Color::srgba(0., 1., 0., 1.),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(HealthBar);
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("units/meteor_small.png"),
+                sprite: Sprite {
+                    color: Color::srgba(0.6, 0.9, 1., 1.),
+                    custom_size: Some(Vec2::new(120., 120.)),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(RenderLayers::layer(1));
+    })
+    .id()
+}
+
+fn spawn_pirate_base(
+    cmd: &mut Commands,
+    spawn_pos: Vec3,
+    asset_server: &Res<AssetServer>,
+) -> Entity {
+    let mut attack_timer = Timer::from_seconds(0.8, TimerMode::Once);
+    attack_timer.tick(std::time::Duration::from_secs(1));
+    cmd.spawn(SpatialBundle {
+        transform: Transform::from_translation(spawn_pos.truncate().extend(-2.)),
+        ..Default::default()
+    })
+    .insert(Collider::cuboid(60.0, 60.0))
+    .insert(Sensor)
+    .insert(Health {
+        current: 1200.,
+        max_health: 1200.,
+    })
+    .insert(Armor {
+        kinetic: 0.4,
+        energy: 0.2,
+        explosive: -0.25,
+    })
+    .insert(Team(1))
+    .insert(PirateBase)
+    .insert(StaticDefense)
+    .insert(Veterancy::default())
+    .insert(AttackComponent {
+        attack_range: 400.,
+        attack_amount: 15.,
+        time_between_attacks: attack_timer,
+        damage_type: DamageType::Kinetic,
+        projectile: ProjectileKind::Ballistic,
+        splash: None,
+    })
+    .with_children(|parent| {
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("units/station_A.png"),
+                sprite: Sprite {
+                    color: Color::srgb(1., 0.3, 0.3),
+                    custom_size: Some(Vec2::new(140., 140.)),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(FaceMovementDirection {
+                face_to_pos: Vec3::ZERO,
+            });
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("healthbar.png"),
+                transform: Transform::from_translation(Vec3::new(0., -80., 0.)),
+                sprite: Sprite {
+                    color: Color::srgba(0., 1., 0., 1.),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(HealthBar);
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("units/meteor_small.png"),
+                sprite: Sprite {
+                    color: Color::srgba(1., 0., 0., 1.),
+                    custom_size: Some(Vec2::new(200., 200.)),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(RenderLayers::layer(1));
+    })
+    .id()
+}
+
+fn move_convoys(time: Res<Time>, mut convoys: Query<(&mut Transform, &ConvoyShip)>) {
+    for (mut tr, convoy) in convoys.iter_mut() {
+        let diff = (convoy.destination - tr.translation).truncate().extend(0.);
+        let step = convoy.speed * time.delta_seconds();
+        if diff.length() <= step {
+            tr.translation = convoy.destination.truncate().extend(tr.translation.z);
+        } else {
+            tr.translation += diff.normalize() * step;
+        }
+    }
+}
+
+fn update_objectives(
+    time: Res<Time>,
+    mut objectives: ResMut<Objectives>,
+    mother_unit: Query<(&Transform, &Team), With<MotherUnit>>,
+    end_points: Query<&Transform, With<EndPoint>>,
+    units: Query<(&Transform, &Team, Has<Structure>), With<Health>>,
+    ledger: Res<ResourceLedger>,
+    (local_player, diplomacy): (Res<LocalPlayer>, Res<Diplomacy>),
+) {
+    let mother_pos = mother_unit
+        .iter()
+        .find(|(_, team)| team.0 == local_player.0)
+        .map(|(tr, _)| tr.translation);
+    for objective in objectives.0.iter_mut() {
+        if objective.status != ObjectiveStatus::Active
+            && !matches!(
+                objective.kind,
+                ObjectiveKind::ReachPoint { .. } | ObjectiveKind::MineMinerals { .. }
+            )
+        {
+            continue;
+        }
+        match &mut objective.kind {
+            //Only done while the mothership is there
+            ObjectiveKind::ReachPoint { target, radius } => {
+                if target.is_none() {
+                    *target = end_points.iter().next().map(|tr| tr.translation);
+                }
+                let reached = target.zip(mother_pos).is_some_and(|(target, mother_pos)| {
+                    (mother_pos - target).truncate().length() < *radius
+                });
+                objective.status = if reached {
+                    ObjectiveStatus::Completed
+                } else {
+                    ObjectiveStatus::Active
+                };
+                objective.progress = match (target, mother_pos) {
+                    (None, _) => "waiting for the point".to_string(),
+                    (Some(target), Some(mother_pos)) => {
+                        format!("{:.0} away", (*target - mother_pos).truncate().length())
+                    }
+                    (Some(_), None) => String::new(),
+                };
+            }
+            ObjectiveKind::Escort {
+                convoy,
+                destination,
+            } => match units.get(*convoy) {
+                Ok((convoy_tr, _, _)) => {
+                    let distance = (*destination - convoy_tr.translation).truncate().length();
+                    if distance < 30. {
+                        objective.status = ObjectiveStatus::Completed;
+                    }
+                    objective.progress = format!("{:.0} to go", distance);
+                }
+                Err(_) => {
+                    objective.status = ObjectiveStatus::Failed;
+                    objective.progress = "convoy destroyed".to_string();
+                }
+            },
+            ObjectiveKind::DestroyBase { base } => {
+                if units.get(*base).is_err() {
+                    objective.status = ObjectiveStatus::Completed;
+                }
+            }
+            ObjectiveKind::HoldArea {
+                center,
+                radius,
+                held,
+            } => {
+                let mut own_inside = false;
+                let mut hostile_inside = false;
+                for (tr, team, is_structure) in units.iter() {
+                    if (tr.translation - *center).truncate().length() > *radius {
+                        continue;
+                    }
+                    //A turret left behind doesn't hold the area, ships have to
+                    if team.0 == local_player.0 {
+                        own_inside |= !is_structure;
+                    } else if diplomacy.is_hostile(local_player.0, team.0) {
+                        hostile_inside = true;
+                    }
+                }
+                //Losing the area, or having to fight for it, starts the count over
+                if own_inside && !hostile_inside {
+                    held.tick(time.delta());
+                } else {
+                    held.reset();
+                }
+                if held.finished() {
+                    objective.status = ObjectiveStatus::Completed;
+                }
+                objective.progress = format!(
+                    "{:.0}/{:.0}s{}",
+                    held.elapsed_secs(),
+                    held.duration().as_secs_f32(),
+                    if hostile_inside { " contested" } else { "" }
+                );
+            }
+            ObjectiveKind::MineMinerals { amount } => {
+                objective.status = if ledger.extracted >= *amount {
+                    ObjectiveStatus::Completed
+                } else {
+                    ObjectiveStatus::Active
+                };
+                objective.progress = format!("{:.0}/{:.0}", ledger.extracted, amount);
+            }
+        }
+    }
+}
+
+fn resolve_objectives(
+    objectives: Res<Objectives>,
+    mother_unit: Query<&Transform, With<MotherUnit>>,
+    mut main_camera: Query<&mut Transform, (With<MainCamera>, Without<MotherUnit>)>,
+    mut game_phase: ResMut<NextState<GamePhase>>,
+) {
+    if objectives.0.is_empty() {
+        return;
+    }
+    if objectives
+        .0
+        .iter()
+        .any(|o| o.status == ObjectiveStatus::Failed)
+    {
+        game_phase.set(GamePhase::Lost);
+        return;
+    }
+    if objectives
+        .0
+        .iter()
+        .all(|o| o.status == ObjectiveStatus::Completed)
+    {
+        for mother_tr in mother_unit.iter() {
+            for mut cam_tr in main_camera.iter_mut() {
+                cam_tr.translation = mother_tr.translation;
+            }
+        }
+        game_phase.set(GamePhase::Won);
+    }
+}
+
+fn draw_objective_markers(
+    objectives: Res<Objectives>,
+    mut gizmos: Gizmos,
+    mut minimap_gizmos: Gizmos<MiniMapGizmos>,
+) {
+    for objective in objectives.0.iter() {
+        if objective.status != ObjectiveStatus::Active {
+            continue;
+        }
+        let (pos, radius) = match objective.kind {
+            ObjectiveKind::Escort { destination, .. } => (destination, 60.),
+            ObjectiveKind::HoldArea { center, radius, .. } => (center, radius),
+            _ => continue,
+        };
+        let color = Color::srgba(1., 1., 0., 0.6);
+        gizmos.circle_2d(pos.truncate(), radius, color);
+        minimap_gizmos.circle_2d(pos.truncate(), radius, color);
+    }
+}
@@ -0,0 +1,298 @@
+use crate::diplomacy::LocalPlayer;
+use crate::materials::{ResourceCost, ResourceLedger};
+use crate::selection::Team;
+use crate::units::{AttackComponent, Health, MiningComponent, MotherUnit, UnitArchetype, Velocity};
+use crate::AppState;
+use crate::GamePhase;
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+pub struct ResearchPlugin;
+
+impl Plugin for ResearchPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), reset_research);
+        app.add_systems(
+            Update,
+            (resolve_research_requests, progress_research, apply_upgrades)
+                .run_if(in_state(GamePhase::Playing)),
+        );
+        app.add_event::<ResearchRequest>();
+        app.init_resource::<Research>();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Upgrade {
+    MiningSpeed,
+    CargoCapacity,
+    WeaponDamage,
+    HullPlating,
+    Engines,
+}
+
+impl Upgrade {
+    pub const ALL: [Upgrade; 5] = [
+        Upgrade::MiningSpeed,
+        Upgrade::CargoCapacity,
+        Upgrade::WeaponDamage,
+        Upgrade::HullPlating,
+        Upgrade::Engines,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Upgrade::MiningSpeed => "Mining lasers",
+            Upgrade::CargoCapacity => "Cargo holds",
+            Upgrade::WeaponDamage => "Weapons",
+            Upgrade::HullPlating => "Hull plating",
+            Upgrade::Engines => "Engines",
+        }
+    }
+
+    pub fn cost(&self) -> ResourceCost {
+        match self {
+            Upgrade::MiningSpeed => ResourceCost::new(60., 0., 0.),
+            Upgrade::CargoCapacity => ResourceCost::new(80., 20., 0.),
+            Upgrade::WeaponDamage => ResourceCost::new(80., 0., 20.),
+            Upgrade::HullPlating => ResourceCost::new(100., 0., 40.),
+            Upgrade::Engines => ResourceCost::new(50., 40., 0.),
+        }
+    }
+
+    //Seconds at the mothership
+    pub fn duration(&self) -> f32 {
+        match self {
+            Upgrade::MiningSpeed => 20.,
+            Upgrade::CargoCapacity => 30.,
+            Upgrade::WeaponDamage => 30.,
+            Upgrade::HullPlating => 40.,
+            Upgrade::Engines => 25.,
+        }
+    }
+
+    pub fn prerequisites(&self) -> &'static [Upgrade] {
+        match self {
+            Upgrade::MiningSpeed => &[],
+            Upgrade::CargoCapacity => &[Upgrade::MiningSpeed],
+            Upgrade::WeaponDamage => &[],
+            Upgrade::HullPlating => &[Upgrade::WeaponDamage],
+            Upgrade::Engines => &[Upgrade::MiningSpeed],
+        }
+    }
+
+    pub fn affects(&self, archetype: UnitArchetype) -> bool {
+        match self {
+            Upgrade::MiningSpeed | Upgrade::CargoCapacity => archetype == UnitArchetype::Miner,
+            Upgrade::WeaponDamage => matches!(
+                archetype,
+                UnitArchetype::Mothership
+                    | UnitArchetype::Melee
+                    | UnitArchetype::Ranged
+                    | UnitArchetype::Artillery
+                    | UnitArchetype::DefenseTurret
+            ),
+            Upgrade::HullPlating | Upgrade::Engines => matches!(
+                archetype,
+                UnitArchetype::Mothership
+                    | UnitArchetype::Miner
+                    | UnitArchetype::Melee
+                    | UnitArchetype::Ranged
+                    | UnitArchetype::Artillery
+                    | UnitArchetype::Repair
+            ),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Research {
+    pub completed: HashSet<Upgrade>,
+    pub current: Option<(Upgrade, Timer)>,
+}
+
+impl Research {
+    pub fn is_available(&self, upgrade: Upgrade) -> bool {
+        !self.completed.contains(&upgrade)
+            && upgrade
+                .prerequisites()
+                .iter()
+                .all(|prerequisite| self.completed.contains(prerequisite))
+    }
+}
+
+//Sent by the research panel
+#[derive(Event)]
+pub struct ResearchRequest(pub Upgrade);
+
+//Upgrades already applied to a unit, so none is applied twice
+#[derive(Component, Default)]
+pub struct AppliedUpgrades(pub HashSet<Upgrade>);
+
+fn reset_research(mut research: ResMut<Research>) {
+    *research = Research::default();
+}
+
+fn resolve_research_requests(
+    mut ev_request: EventReader<ResearchRequest>,
+    mut research: ResMut<Research>,
+    mut ledger: ResMut<ResourceLedger>,
+) {
+    for request in ev_request.read() {
+        let upgrade = request.0;
+        if research.current.is_some() || !research.is_available(upgrade) {
+            continue;
+        }
+        if ledger.spend(&upgrade.cost()) {
+            research.current = Some((
+                upgrade,
+                Timer::from_seconds(upgrade.duration(), TimerMode::Once),
+            ));
+        }
+    }
+}
+
+//Research only goes on while the mothership is alive
+fn progress_research(
+    time: Res<Time>,
+    mut research: ResMut<Research>,
+    mother_unit: Query<&Team, With<MotherUnit>>,
+    local_player: Res<LocalPlayer>,
+) {
+    if !mother_unit.iter().any(|team| team.0 == local_player.0) {
+        return;
+    }
+    let mut finished = None;
+    if let Some((upgrade, timer)) = research.current.as_mut() {
+        timer.tick(time.delta());
+        if timer.finished() {
+            finished = Some(*upgrade);
+        }
+    }
+    if let Some(upgrade) = finished {
+        research.current = None;
+        research.completed.insert(upgrade);
+    }
+}
+
+type UpgradableUnits<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static UnitArchetype,
+        &'static Team,
+        Option<&'static mut AppliedUpgrades>,
+        Option<&'static mut MiningComponent>,
+        Option<&'static mut AttackComponent>,
+        Option<&'static mut Health>,
+        Option<&'static mut Velocity>,
+    ),
+>;
+
+//Works for ships built before and after the research finished
+fn apply_upgrades(
+    mut cmd: Commands,
+    research: Res<Research>,
+    local_player: Res<LocalPlayer>,
+    mut units: UpgradableUnits,
+) {
+    for (e, archetype, team, applied, mut mining_comp, mut attack_comp, mut hp, mut vel) in
+        units.iter_mut()
+    {
+        if team.0 != local_player.0 {
+            continue;
+        }
+        let mut newly_applied = HashSet::new();
+        for upgrade in research.completed.iter() {
+            if !upgrade.affects(*archetype)
+                || applied.as_ref().is_some_and(|a| a.0.contains(upgrade))
+            {
+                continue;
+            }
+            match upgrade {
+                Upgrade::MiningSpeed => {
+                    if let Some(mining_comp) = mining_comp.as_mut() {
+                        let faster = mining_comp.time_between_mine.duration().mul_f32(0.75);
+                        mining_comp.time_between_mine.set_duration(faster);
+                    }
+                }
+                Upgrade::CargoCapacity => {
+                    if let Some(mining_comp) = mining_comp.as_mut() {
+                        mining_comp.max_carry += 5.;
+                    }
+                }
+                Upgrade::WeaponDamage => {
+                    if let Some(attack_comp) = attack_comp.as_mut() {
+                        attack_comp.attack_amount *= 1.2;
+                    }
+                }
+                Upgrade::HullPlating => {
+                    if let Some(hp) = hp.as_mut() {
+                        let bonus_health = hp.max_health * 0.2;
+                        hp.max_health += bonus_health;
+                        hp.current += bonus_health;
+                    }
+                }
+                Upgrade::Engines => {
+                    if let Some(vel) = vel.as_mut() {
+                        vel.0 *= 1.2;
+                    }
+                }
+            }
+            newly_applied.insert(*upgrade);
+        }
+        if newly_applied.is_empty() {
+            continue;
+        }
+        if let Some(mut applied) = applied {
+            applied.0.extend(newly_applied);
+        } else {
+            cmd.entity(e).try_insert(AppliedUpgrades(newly_applied));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upgrades_without_prerequisites_start_available() {
+        let research = Research::default();
+        assert!(research.is_available(Upgrade::MiningSpeed));
+        assert!(research.is_available(Upgrade::WeaponDamage));
+    }
+
+    #[test]
+    fn prerequisites_have_to_be_completed_first() {
+        let mut research = Research::default();
+        assert!(!research.is_available(Upgrade::CargoCapacity));
+        assert!(!research.is_available(Upgrade::Engines));
+        research.completed.insert(Upgrade::MiningSpeed);
+        assert!(research.is_available(Upgrade::CargoCapacity));
+        assert!(research.is_available(Upgrade::Engines));
+        assert!(!research.is_available(Upgrade::HullPlating));
+    }
+
+    #[test]
+    fn completed_upgrades_are_not_available_again() {
+        let mut research = Research::default();
+        research.completed.insert(Upgrade::WeaponDamage);
+        assert!(!research.is_available(Upgrade::WeaponDamage));
+        assert!(research.is_available(Upgrade::HullPlating));
+    }
+
+    #[test]
+    fn every_upgrade_can_be_reached() {
+        let mut research = Research::default();
+        for _ in 0..Upgrade::ALL.len() {
+            for upgrade in Upgrade::ALL {
+                if research.is_available(upgrade) {
+                    research.completed.insert(upgrade);
+                }
+            }
+        }
+        assert_eq!(research.completed.len(), Upgrade::ALL.len());
+    }
+}
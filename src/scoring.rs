@@ -0,0 +1,122 @@
+use crate::diplomacy::{Diplomacy, LocalPlayer};
+use crate::materials::ResourceLedger;
+use crate::selection::Team;
+use crate::units::{EnemyBrain, UnitArchetype};
+use crate::AppState;
+use crate::GamePhase;
+use bevy::prelude::*;
+
+pub struct ScoringPlugin;
+
+impl Plugin for ScoringPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), reset_score);
+        app.add_systems(Update, update_score.run_if(in_state(GamePhase::Playing)));
+        app.init_resource::<Score>();
+        app.init_resource::<ExtractionQuota>();
+    }
+}
+
+//Points for every wave the fleet wiped out
+pub const POINTS_PER_WAVE: f32 = 50.;
+//Extracting before this many seconds gives a point for each second left
+pub const TIME_PAR: f32 = 600.;
+
+//Minerals the mothership has to bring, toggled from the main menu
+#[derive(Resource)]
+pub struct ExtractionQuota {
+    pub enabled: bool,
+    pub amount: f32,
+}
+
+impl Default for ExtractionQuota {
+    fn default() -> ExtractionQuota {
+        ExtractionQuota {
+            enabled: false,
+            amount: 500.,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Score {
+    pub elapsed: f32,
+    pub minerals: f32,
+    pub fleet: f32,
+    pub waves_defeated: i32,
+    pub wave_reached: i32,
+}
+
+impl Score {
+    pub fn wave_points(&self) -> f32 {
+        self.waves_defeated as f32 * POINTS_PER_WAVE
+    }
+
+    pub fn time_bonus(&self) -> f32 {
+        (TIME_PAR - self.elapsed).max(0.)
+    }
+
+    pub fn total(&self) -> f32 {
+        self.minerals + self.fleet + self.wave_points() + self.time_bonus()
+    }
+
+    //Survival time as minutes:seconds, what endless runs are measured by
+    pub fn survival_time(&self) -> String {
+        let secs = self.elapsed as i32;
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+
+    pub fn grade(&self) -> &'static str {
+        let total = self.total();
+        if total >= 3000. {
+            "S"
+        } else if total >= 1500. {
+            "A"
+        } else if total >= 800. {
+            "B"
+        } else if total >= 400. {
+            "C"
+        } else {
+            "D"
+        }
+    }
+}
+
+fn reset_score(mut score: ResMut<Score>) {
+    *score = Score::default();
+}
+
+fn update_score(
+    mut score: ResMut<Score>,
+    time: Res<Time>,
+    ledger: Res<ResourceLedger>,
+    enemy_brain: Res<EnemyBrain>,
+    units: Query<(&UnitArchetype, &Team)>,
+    local_player: Res<LocalPlayer>,
+    diplomacy: Res<Diplomacy>,
+) {
+    score.elapsed += time.delta_seconds();
+    score.minerals = ledger.extracted;
+
+    //Surviving ships are worth half of what they cost, the mothership and structures don't count
+    let mut fleet = 0.;
+    let mut hostiles_alive = false;
+    for (archetype, team) in units.iter() {
+        if team.0 == local_player.0 {
+            if archetype.supply() > 0 {
+                fleet += archetype.cost() * 0.5;
+            }
+        } else if diplomacy.is_hostile(local_player.0, team.0) {
+            hostiles_alive = true;
+        }
+    }
+    score.fleet = fleet;
+
+    score.wave_reached = enemy_brain.current_wave;
+    //The latest wave only counts once none of its ships are left
+    score.waves_defeated = if hostiles_alive {
+        (enemy_brain.current_wave - 1).max(0)
+    } else {
+        enemy_brain.current_wave
+    };
+}
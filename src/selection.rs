@@ -1,3 +1,5 @@
+use crate::diplomacy::LocalPlayer;
+use crate::structures::PlacementMode;
 use crate::{DontDestroyOnLoad, MainCamera};
 use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
@@ -66,7 +68,13 @@ fn check_selection(
     mut rect_selection: ResMut<RectSelection>,
     mut gizmos: Gizmos,
     team_q: Query<&Team>,
+    local_player: Res<LocalPlayer>,
+    placement: Res<PlacementMode>,
 ) {
+    //Clicks are used to place structures while a ghost is shown
+    if placement.blocks_input() {
+        return;
+    }
     //Get world position of mouse
     let (camera, camera_transform) = q_camera.single();
     let window = q_window.single();
@@ -86,7 +94,7 @@ fn check_selection(
         let filter = QueryFilter::default();
         rapier_context.intersections_with_point(click_pos, filter, |entity| {
             if let Ok(team_of_entity) = team_q.get(entity) {
-                if team_of_entity.0 == 0 {
+                if team_of_entity.0 == local_player.0 {
                     if keyboard_input.pressed(KeyCode::ControlLeft) {
                         selected_new_unit = true;
 
@@ -142,7 +150,7 @@ fn check_selection(
                 filter,
                 |entity| {
                     if let Ok(team_of_entity) = team_q.get(entity) {
-                        if team_of_entity.0 == 0 {
+                        if team_of_entity.0 == local_player.0 {
                             if !currently_selected.ent.contains(&entity) {
                                 currently_selected.ent.push(entity);
                             }
@@ -0,0 +1,662 @@
+use crate::diplomacy::{Diplomacy, LocalPlayer};
+use crate::materials::{Mineable, MiningSlots, ResourceCost, ResourceLedger};
+use crate::movement::{FaceMovementDirection, MotionTracker};
+use crate::selection::{CurrentlySelected, Selectable, Team};
+use crate::units::{
+    closest_hostile, lead_target, spawn_bullet, Armor, AttackComponent, CargoDepot, DamageType,
+    Health, HealthBar, MiningComponent, ProjectileKind, SupplyProvider, UnitArchetype, UnitCommand,
+    UnitCommandList, Veterancy, BULLET_SPEED,
+};
+use crate::AppState;
+use crate::GamePhase;
+use crate::MainCamera;
+use crate::MapBoundaries;
+use crate::MiniMapGizmos;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::render::view::visibility::RenderLayers;
+use bevy::window::PrimaryWindow;
+use bevy_rapier2d::prelude::*;
+
+pub struct StructuresPlugin;
+
+impl Plugin for StructuresPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), reset_placement);
+        app.add_systems(
+            Update,
+            (
+                start_placement,
+                update_placement_ghost,
+                confirm_placement,
+                progress_construction,
+                fire_static_defenses,
+                mine_with_outposts,
+                sweep_sensor_beacons,
+            )
+                .run_if(in_state(GamePhase::Playing)),
+        );
+        app.init_resource::<PlacementMode>();
+    }
+}
+
+//Builders have to be this close to a construction site to work on it
+pub const BUILD_RANGE: f32 = 120.;
+//Structures snap to a grid of this size when placed
+const PLACEMENT_GRID: f32 = 50.;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StructureKind {
+    DefenseTurret,
+    MiningOutpost,
+    SensorBeacon,
+    CargoDepot,
+    SupplyDepot,
+}
+
+impl StructureKind {
+    pub fn archetype(&self) -> UnitArchetype {
+        match self {
+            StructureKind::DefenseTurret => UnitArchetype::DefenseTurret,
+            StructureKind::MiningOutpost => UnitArchetype::MiningOutpost,
+            StructureKind::SensorBeacon => UnitArchetype::SensorBeacon,
+            StructureKind::CargoDepot => UnitArchetype::CargoDepot,
+            StructureKind::SupplyDepot => UnitArchetype::SupplyDepot,
+        }
+    }
+
+    pub fn price(&self) -> ResourceCost {
+        self.archetype().price()
+    }
+
+    //Seconds of work for a single builder
+    pub fn build_time(&self) -> f32 {
+        match self {
+            StructureKind::DefenseTurret => 12.,
+            StructureKind::MiningOutpost => 15.,
+            StructureKind::SensorBeacon => 8.,
+            StructureKind::CargoDepot => 10.,
+            StructureKind::SupplyDepot => 10.,
+        }
+    }
+
+    pub fn half_size(&self) -> f32 {
+        match self {
+            StructureKind::DefenseTurret => 30.,
+            StructureKind::MiningOutpost => 40.,
+            StructureKind::SensorBeacon => 20.,
+            StructureKind::CargoDepot => 35.,
+            StructureKind::SupplyDepot => 30.,
+        }
+    }
+
+    fn texture(&self) -> &'static str {
+        match self {
+            StructureKind::DefenseTurret => "units/station_B.png",
+            StructureKind::MiningOutpost => "units/station_A.png",
+            StructureKind::SensorBeacon => "units/station_B.png",
+            StructureKind::CargoDepot => "units/station_A.png",
+            StructureKind::SupplyDepot => "units/station_B.png",
+        }
+    }
+
+    fn tint(&self) -> Color {
+        match self {
+            StructureKind::DefenseTurret => Color::srgba(1., 1., 1., 1.),
+            StructureKind::MiningOutpost => Color::srgba(1., 0.85, 0.5, 1.),
+            StructureKind::SensorBeacon => Color::srgba(0.5, 0.9, 1., 1.),
+            StructureKind::CargoDepot => Color::srgba(0.6, 1., 0.6, 1.),
+            StructureKind::SupplyDepot => Color::srgba(1., 1., 0.5, 1.),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Structure {
+    pub kind: StructureKind,
+}
+
+#[derive(Component)]
+pub struct UnderConstruction {
+    pub progress: Timer,
+}
+
+//Sprite of a structure, faded out until construction is done
+#[derive(Component)]
+pub struct StructureSprite;
+
+//Turrets pick their own targets instead of following a command list
+#[derive(Component)]
+pub struct StaticDefense;
+
+#[derive(Component)]
+pub struct MiningOutpost {
+    pub range: f32,
+    pub yield_per_tick: f32,
+    pub time_between_mine: Timer,
+    //Asteroid the outpost holds a mining slot on
+    pub target: Option<Entity>,
+}
+
+#[derive(Component)]
+pub struct SensorBeacon {
+    pub radius: f32,
+}
+
+#[derive(Component)]
+pub struct PlacementGhost;
+
+#[derive(Resource, Default)]
+pub struct PlacementMode {
+    pub kind: Option<StructureKind>,
+    pub position: Vec3,
+    pub valid: bool,
+    //Set when a click was used by placement, so selection and orders ignore it until released
+    pub swallow_click: bool,
+}
+
+impl PlacementMode {
+    pub fn blocks_input(&self) -> bool {
+        self.kind.is_some() || self.swallow_click
+    }
+}
+
+fn reset_placement(mut placement: ResMut<PlacementMode>) {
+    *placement = PlacementMode::default();
+}
+
+//Selected miners, the only ships that know how to build
+#[derive(SystemParam)]
+struct SelectedBuilders<'w, 's> {
+    currently_selected: Res<'w, CurrentlySelected>,
+    builders: Query<'w, 's, &'static mut UnitCommandList, With<MiningComponent>>,
+}
+
+#[derive(SystemParam)]
+struct Placement<'w, 's> {
+    mode: ResMut<'w, PlacementMode>,
+    ghost_q: Query<'w, 's, Entity, With<PlacementGhost>>,
+}
+
+fn start_placement(
+    mut cmd: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    selected_builders: SelectedBuilders,
+    ledger: Res<ResourceLedger>,
+    placement: Placement,
+    asset_server: Res<AssetServer>,
+) {
+    let SelectedBuilders {
+        currently_selected,
+        builders,
+    } = selected_builders;
+    let Placement {
+        mode: mut placement,
+        ghost_q,
+    } = placement;
+    let mut requested = None;
+    if keyboard_input.just_pressed(KeyCode::KeyB) {
+        requested = Some(StructureKind::DefenseTurret);
+    } else if keyboard_input.just_pressed(KeyCode::KeyN) {
+        requested = Some(StructureKind::MiningOutpost);
+    } else if keyboard_input.just_pressed(KeyCode::KeyM) {
+        requested = Some(StructureKind::SensorBeacon);
+    } else if keyboard_input.just_pressed(KeyCode::KeyV) {
+        requested = Some(StructureKind::CargoDepot);
+    } else if keyboard_input.just_pressed(KeyCode::KeyJ) {
+        requested = Some(StructureKind::SupplyDepot);
+    }
+    let Some(kind) = requested else {
+        return;
+    };
+    //Only miners know how to build
+    if !currently_selected
+        .ent
+        .iter()
+        .any(|e| builders.get(*e).is_ok())
+    {
+        return;
+    }
+    if !ledger.can_afford(&kind.price()) {
+        return;
+    }
+
+    for ghost in ghost_q.iter() {
+        cmd.entity(ghost).despawn_recursive();
+    }
+    placement.kind = Some(kind);
+    placement.valid = false;
+    cmd.spawn(SpriteBundle {
+        texture: asset_server.load(kind.texture()),
+        transform: Transform::from_translation(Vec3::new(0., 0., 5.)),
+        sprite: Sprite {
+            color: Color::srgba(0., 1., 0., 0.4),
+            custom_size: Some(Vec2::splat(kind.half_size() * 2.)),
+            ..default()
+        },
+        ..Default::default()
+    })
+    .insert(PlacementGhost);
+}
+
+fn update_placement_ghost(
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut placement: ResMut<PlacementMode>,
+    mut ghost_q: Query<(&mut Transform, &mut Sprite), With<PlacementGhost>>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    rapier_context: Res<RapierContext>,
+    boundaries: Res<MapBoundaries>,
+) {
+    if placement.swallow_click
+        && !buttons.pressed(MouseButton::Left)
+        && !buttons.pressed(MouseButton::Right)
+    {
+        placement.swallow_click = false;
+    }
+    let Some(kind) = placement.kind else {
+        return;
+    };
+
+    let (camera, camera_transform) = q_camera.single();
+    let window = q_window.single();
+    let Some(cursor_pos) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+        .map(|ray| ray.origin.truncate())
+    else {
+        return;
+    };
+
+    let half_size = kind.half_size();
+    let snapped = (cursor_pos / PLACEMENT_GRID).round() * PLACEMENT_GRID;
+    let pos = Vec2::new(
+        snapped.x.clamp(
+            boundaries.x_boundaries.x + half_size,
+            boundaries.x_boundaries.y - half_size,
+        ),
+        snapped.y.clamp(
+            boundaries.y_boundaries.x + half_size,
+            boundaries.y_boundaries.y - half_size,
+        ),
+    );
+
+    //Anything with a collider in the footprint blocks the spot
+    let mut blocked = false;
+    let shape = Collider::cuboid(half_size, half_size);
+    rapier_context.intersections_with_shape(pos, 0., &shape, QueryFilter::default(), |_| {
+        blocked = true;
+        false
+    });
+
+    placement.position = pos.extend(0.);
+    placement.valid = !blocked;
+    for (mut ghost_tr, mut ghost_sprite) in ghost_q.iter_mut() {
+        ghost_tr.translation = pos.extend(5.);
+        if placement.valid {
+            ghost_sprite.color = Color::srgba(0., 1., 0., 0.4);
+        } else {
+            ghost_sprite.color = Color::srgba(1., 0., 0., 0.4);
+        }
+    }
+}
+
+fn confirm_placement(
+    mut cmd: Commands,
+    (buttons, keyboard_input): (Res<ButtonInput<MouseButton>>, Res<ButtonInput<KeyCode>>),
+    selected_builders: SelectedBuilders,
+    mut ledger: ResMut<ResourceLedger>,
+    placement: Placement,
+    local_player: Res<LocalPlayer>,
+    asset_server: Res<AssetServer>,
+) {
+    let SelectedBuilders {
+        currently_selected,
+        mut builders,
+    } = selected_builders;
+    let Placement {
+        mode: mut placement,
+        ghost_q,
+    } = placement;
+    let Some(kind) = placement.kind else {
+        return;
+    };
+    let cancel = buttons.just_pressed(MouseButton::Right);
+    let confirm = buttons.just_pressed(MouseButton::Left)
+        && placement.valid
+        && ledger.can_afford(&kind.price());
+    if !cancel && !confirm {
+        return;
+    }
+
+    if confirm {
+        ledger.spend(&kind.price());
+        let site = spawn_structure(
+            &mut cmd,
+            kind,
+            placement.position,
+            local_player.0,
+            &asset_server,
+        );
+        for e in currently_selected.ent.iter() {
+            if let Ok(mut command_list) = builders.get_mut(*e) {
+                if !keyboard_input.pressed(KeyCode::ShiftLeft) {
+                    command_list.commands = Vec::new();
+                }
+                command_list
+                    .commands
+                    .push(UnitCommand::BuildStructure(site));
+            }
+        }
+    }
+
+    for ghost in ghost_q.iter() {
+        cmd.entity(ghost).despawn_recursive();
+    }
+    placement.kind = None;
+    placement.swallow_click = true;
+}
+
+pub fn spawn_structure(
+    cmd: &mut Commands,
+    kind: StructureKind,
+    spawn_pos: Vec3,
+    team: i32,
+    asset_server: &Res<AssetServer>,
+) -> Entity {
+    let half_size = kind.half_size();
+    let max_health = match kind {
+        StructureKind::DefenseTurret => 400.,
+        StructureKind::MiningOutpost => 500.,
+        StructureKind::SensorBeacon => 200.,
+        StructureKind::CargoDepot => 350.,
+        StructureKind::SupplyDepot => 300.,
+    };
+    let mut structure = cmd.spawn(SpatialBundle {
+        transform: Transform::from_translation(spawn_pos.truncate().extend(-2.)),
+        ..Default::default()
+    });
+    structure
+        .insert(Collider::cuboid(half_size, half_size))
+        .insert(Sensor)
+        .insert(Selectable)
+        .insert(Team(team))
+        .insert(kind.archetype())
+        .insert(Structure { kind })
+        .insert(UnderConstruction {
+            progress: Timer::from_seconds(kind.build_time(), TimerMode::Once),
+        })
+        .insert(Health {
+            current: max_health * 0.1,
+            max_health,
+        })
+        .insert(Armor {
+            kinetic: 0.3,
+            energy: 0.,
+            explosive: -0.25,
+        });
+
+    match kind {
+        StructureKind::DefenseTurret => {
+            let mut attack_timer = Timer::from_seconds(0.6, TimerMode::Once);
+            attack_timer.tick(std::time::Duration::from_secs(1));
+            structure
+                .insert(StaticDefense)
+                .insert(Veterancy::default())
+                .insert(AttackComponent {
+                    attack_range: 350.,
+                    attack_amount: 12.,
+                    time_between_attacks: attack_timer,
+                    damage_type: DamageType::Kinetic,
+                    projectile: ProjectileKind::Ballistic,
+                    splash: None,
+                });
+        }
+        StructureKind::MiningOutpost => {
+            structure.insert(MiningOutpost {
+                range: 350.,
+                yield_per_tick: 1.,
+                time_between_mine: Timer::from_seconds(1., TimerMode::Repeating),
+                target: None,
+            });
+        }
+        StructureKind::SensorBeacon => {
+            structure.insert(SensorBeacon { radius: 1200. });
+        }
+        StructureKind::CargoDepot => {
+            structure.insert(CargoDepot);
+        }
+        StructureKind::SupplyDepot => {
+            structure.insert(SupplyProvider(10));
+        }
+    }
+
+    structure.with_children(|parent| {
+        let mut sprite = parent.spawn(SpriteBundle {
+            texture: asset_server.load(kind.texture()),
+            sprite: Sprite {
+                color: kind.tint().with_alpha(0.4),
+                custom_size: Some(Vec2::splat(half_size * 2.)),
+                ..default()
+            },
+            ..Default::default()
+        });
+        sprite.insert(StructureSprite);
+        if kind == StructureKind::DefenseTurret {
+            sprite.insert(FaceMovementDirection {
+                face_to_pos: spawn_pos + Vec3::Y,
+            });
+        }
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("healthbar.png"),
+                transform: Transform::from_translation(Vec3::new(0., -half_size - 10., 0.)),
+                sprite: Sprite {
+                    color: Color::srgba(0., 1., 0., 1.),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(HealthBar);
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("units/meteor_small.png"),
+                sprite: Sprite {
+                    color: Color::srgba(0., 1., 0., 1.),
+                    custom_size: Some(Vec2::splat(half_size * 4.)),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(RenderLayers::layer(1));
+    });
+    structure.id()
+}
+
+//Every builder in range speeds construction up, health grows along with the progress
+fn progress_construction(
+    mut cmd: Commands,
+    time: Res<Time>,
+    mut sites: Query<(
+        Entity,
+        &Transform,
+        &Structure,
+        &mut UnderConstruction,
+        &mut Health,
+        &Children,
+    )>,
+    builders: Query<(&UnitCommandList, &Transform), With<MiningComponent>>,
+    mut sprites: Query<&mut Sprite, With<StructureSprite>>,
+) {
+    for (site_e, site_tr, structure, mut construction, mut hp, children) in sites.iter_mut() {
+        let mut builder_count = 0;
+        for (command_list, builder_tr) in builders.iter() {
+            if let Some(UnitCommand::BuildStructure(target)) = command_list.commands.first() {
+                if *target == site_e
+                    && (builder_tr.translation - site_tr.translation).length() < BUILD_RANGE
+                {
+                    builder_count += 1;
+                }
+            }
+        }
+        if builder_count == 0 {
+            continue;
+        }
+
+        let before = construction.progress.fraction();
+        construction
+            .progress
+            .tick(time.delta().mul_f32(builder_count as f32));
+        let gained = construction.progress.fraction() - before;
+        hp.current = (hp.current + hp.max_health * 0.9 * gained).min(hp.max_health);
+
+        if construction.progress.finished() {
+            cmd.entity(site_e).remove::<UnderConstruction>();
+            for child in children.iter() {
+                if let Ok(mut sprite) = sprites.get_mut(*child) {
+                    sprite.color = structure.kind.tint();
+                }
+            }
+        }
+    }
+}
+
+type Turrets<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut AttackComponent,
+        &'static Team,
+        &'static Children,
+    ),
+    (With<StaticDefense>, Without<UnderConstruction>),
+>;
+
+//Same target choice as mildly aggressive units, but turrets never leave their spot
+fn fire_static_defenses(
+    mut cmd: Commands,
+    mut turrets: Turrets,
+    all_units: Query<(&Transform, &Team, Entity)>,
+    motion_q: Query<&MotionTracker>,
+    mut face_direction_q: Query<&mut FaceMovementDirection>,
+    diplomacy: Res<Diplomacy>,
+    asset_server: Res<AssetServer>,
+) {
+    for (turret_e, mut attack_comp, turret_team, children) in turrets.iter_mut() {
+        let Ok((turret_tr, _, _)) = all_units.get(turret_e) else {
+            continue;
+        };
+        let turret_pos = turret_tr.translation;
+        let Some((enemy, distance)) =
+            closest_hostile(turret_pos, turret_team.0, &all_units, &diplomacy)
+        else {
+            continue;
+        };
+        if distance > attack_comp.attack_range {
+            continue;
+        }
+        let Ok((enemy_tr, _, _)) = all_units.get(enemy) else {
+            continue;
+        };
+        for child in children {
+            if let Ok(mut face_dir) = face_direction_q.get_mut(*child) {
+                face_dir.face_to_pos = enemy_tr.translation;
+                break;
+            }
+        }
+        if attack_comp.time_between_attacks.finished() {
+            attack_comp.time_between_attacks.reset();
+            let mut aim_pos = enemy_tr.translation;
+            if let Ok(enemy_motion) = motion_q.get(enemy) {
+                aim_pos = lead_target(
+                    turret_pos,
+                    enemy_tr.translation,
+                    enemy_motion.velocity,
+                    BULLET_SPEED,
+                );
+            }
+            spawn_bullet(
+                &mut cmd,
+                &attack_comp,
+                turret_pos - Vec3::new(0., 0., 1.),
+                (turret_e, Some(turret_team.0)),
+                enemy,
+                aim_pos,
+                &asset_server,
+            );
+        }
+    }
+}
+
+//Outposts slowly drain the closest asteroid in range without any miners, holding a mining slot like one
+fn mine_with_outposts(
+    time: Res<Time>,
+    mut outposts: Query<(Entity, &Transform, &mut MiningOutpost), Without<UnderConstruction>>,
+    mut mineables: Query<(Entity, &Transform, &mut Mineable, Option<&mut MiningSlots>)>,
+    mut ledger: ResMut<ResourceLedger>,
+    mut gizmos: Gizmos,
+) {
+    for (outpost_e, outpost_tr, mut outpost) in outposts.iter_mut() {
+        let mut closest = None;
+        let mut closest_dist = outpost.range;
+        for (mineable_e, mineable_tr, mineable, slots) in mineables.iter() {
+            let dist = (mineable_tr.translation - outpost_tr.translation).length();
+            let slot_free =
+                slots.is_none_or(|slots| slots.slot_of(outpost_e).is_some() || !slots.is_full());
+            if dist < closest_dist && mineable.amount > 0. && slot_free {
+                closest_dist = dist;
+                closest = Some(mineable_e);
+            }
+        }
+        let Some(Ok((mineable_e, mineable_tr, mut mineable, slots))) =
+            closest.map(|e| mineables.get_mut(e))
+        else {
+            outpost.target = None;
+            continue;
+        };
+        let mineable_pos = mineable_tr.translation;
+        if let Some(mut slots) = slots {
+            slots.claim(outpost_e);
+        }
+        outpost.target = Some(mineable_e);
+        gizmos.line(
+            outpost_tr.translation,
+            mineable_pos,
+            Color::srgba(1., 0.85, 0.5, 0.3),
+        );
+        outpost.time_between_mine.tick(time.delta());
+        if outpost.time_between_mine.just_finished() {
+            let mined = outpost.yield_per_tick.min(mineable.amount);
+            mineable.amount -= mined;
+            ledger.deposit(mineable.kind, mined);
+        }
+    }
+}
+
+//Beacons mark hostile ships in their radius on the minimap
+fn sweep_sensor_beacons(
+    beacons: Query<(&Transform, &Team, &SensorBeacon), Without<UnderConstruction>>,
+    all_units: Query<(&Transform, &Team)>,
+    diplomacy: Res<Diplomacy>,
+    mut minimap_gizmos: Gizmos<MiniMapGizmos>,
+) {
+    for (beacon_tr, beacon_team, beacon) in beacons.iter() {
+        minimap_gizmos.circle_2d(
+            beacon_tr.translation.truncate(),
+            beacon.radius,
+            Color::srgba(0.5, 0.9, 1., 0.5),
+        );
+        for (unit_tr, unit_team) in all_units.iter() {
+            if diplomacy.is_hostile(beacon_team.0, unit_team.0)
+                && (unit_tr.translation - beacon_tr.translation).length() < beacon.radius
+            {
+                minimap_gizmos.circle_2d(
+                    unit_tr.translation.truncate(),
+                    80.,
+                    Color::srgb(1., 0., 0.),
+                );
+            }
+        }
+    }
+}
@@ -1,16 +1,35 @@
-use crate::materials::MineralResources;
+use crate::abilities::{Abilities, AbilityRequest};
+use crate::campaign::{save_campaign_progress, Campaign, CAMPAIGN_MISSIONS};
+use crate::difficulty::Difficulty;
+use crate::diplomacy::LocalPlayer;
+use crate::leaderboard::Leaderboard;
+use crate::materials::{MiningSlots, ResourceKind, ResourceLedger};
+use crate::objectives::{ObjectiveStatus, Objectives, Scenario};
+use crate::research::{Research, ResearchRequest, Upgrade};
+use crate::scoring::{ExtractionQuota, Score};
+use crate::selection::CurrentlySelected;
 use crate::selection::Team;
-use crate::units::BuildQueue;
+use crate::structures::UnderConstruction;
+use crate::units::{
+    rank_chevrons, Boss, BuildQueue, CargoDepot, FleetUpkeep, Health, MiningComponent, MotherUnit,
+    Supply, UnitArchetype, Veterancy,
+};
 use crate::AppState;
 use crate::EndGameTimer;
+use crate::GameMode;
 use crate::GamePhase;
+use crate::MainCamera;
+use crate::MapSeed;
 use bevy::color::palettes::basic::*;
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 use bevy::render::camera::RenderTarget;
 use bevy::render::render_resource::{
     Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
 };
 use bevy::render::view::visibility::RenderLayers;
+use bevy::window::PrimaryWindow;
+use bevy_rapier2d::prelude::*;
 
 pub struct UIPlugin;
 
@@ -26,45 +45,90 @@ impl Plugin for UIPlugin {
             (
                 button_system.run_if(
                     in_state(AppState::Menu)
-                        .or_else(in_state(GamePhase::Lost).or_else(in_state(GamePhase::Won))),
+                        .or_else(in_state(AppState::FleetScreen))
+                        .or_else(in_state(GamePhase::Lost).or_else(in_state(GamePhase::Won)))
+                        .or_else(in_state(GamePhase::Survived)),
                 ),
                 update_ui_texts,
                 update_unit_ui_texts,
+                update_selection_panel,
+                update_boss_health_bar,
+                update_ability_buttons,
+                show_asteroid_saturation.run_if(in_state(AppState::InGame)),
+                ability_button_system.run_if(in_state(GamePhase::Playing)),
+                research_button_system.run_if(in_state(GamePhase::Playing)),
+                update_research_panel,
                 update_progress_bar,
                 run_down_welcome_text.run_if(in_state(AppState::InGame)),
                 show_extraction_timer.run_if(in_state(AppState::InGame)),
+                update_score_text.run_if(in_state(AppState::InGame)),
+                update_objectives_text.run_if(in_state(AppState::InGame)),
+                update_menu_options.run_if(in_state(AppState::Menu)),
             ),
         );
         app.add_systems(
             OnEnter(GamePhase::Lost),
             (setup_lose_screen, destroy_all_ui),
         );
-        app.add_systems(OnEnter(GamePhase::Won), (setup_win_screen, destroy_all_ui));
+        app.add_systems(
+            OnEnter(GamePhase::Won),
+            (setup_win_screen, destroy_all_ui).after(save_campaign_progress),
+        );
+        app.add_systems(OnEnter(AppState::FleetScreen), setup_fleet_screen);
+        app.add_systems(
+            OnEnter(GamePhase::Survived),
+            (setup_survival_screen, destroy_all_ui),
+        );
         app.init_resource::<WelcomeText>();
     }
 }
 
 fn update_ui_texts(
     mut resource_text: Query<&mut Text, With<ResourceText>>,
-    mineral_resource: Res<MineralResources>,
+    ledger: Res<ResourceLedger>,
 ) {
     for mut text in resource_text.iter_mut() {
-        text.sections[1].value = format!("{}", mineral_resource.mineral);
+        for (i, kind) in ResourceKind::ALL.iter().enumerate() {
+            text.sections[i * 2 + 1].value = format!("{:.0}  ", ledger.get(*kind));
+        }
     }
 }
 
 fn update_unit_ui_texts(
     mut resource_text: Query<&mut Text, With<UnitText>>,
     ally_units_q: Query<&Team>,
+    local_player: Res<LocalPlayer>,
+    supply: Res<Supply>,
+    upkeep: Res<FleetUpkeep>,
+    depots: Query<&Team, (With<CargoDepot>, Without<UnderConstruction>)>,
+    miners: Query<(&MiningComponent, &Team)>,
 ) {
     let mut count = 0;
     for t in ally_units_q.iter() {
-        if t.0 == 0 {
+        if t.0 == local_player.0 {
             count += 1;
         }
     }
+    //Miners keep their cargo until a depot is available again
+    let cargo_stranded = !depots.iter().any(|t| t.0 == local_player.0)
+        && miners
+            .iter()
+            .any(|(mining_comp, t)| t.0 == local_player.0 && mining_comp.current_carry > 0.);
     for mut text in resource_text.iter_mut() {
         text.sections[1].value = format!("{}", count);
+        text.sections[3].value = format!("{}/{}", supply.used, supply.cap);
+        let mut warning = String::new();
+        if supply.blocked {
+            warning += " SUPPLY BLOCKED";
+        }
+        if cargo_stranded {
+            warning += " NO CARGO DEPOT";
+        }
+        let upkeep_per_second = supply.upkeep_per_second(&upkeep);
+        if upkeep_per_second > 0. {
+            warning += &format!(" upkeep -{:.1} ore/s", upkeep_per_second);
+        }
+        text.sections[4].value = warning;
     }
 }
 
@@ -132,8 +196,10 @@ pub fn spawn_build_order_card(
                     asset_path = "units/station_A.png";
                 } else if unit_type == 1 {
                     asset_path = "units/enemy_A.png";
-                } else if unit_type == 2 {
+                } else if unit_type == 2 || unit_type == 3 {
                     asset_path = "units/ship_basic.png";
+                } else if unit_type == 4 {
+                    asset_path = "units/station_A.png";
                 }
 
                 pp.spawn(ImageBundle {
@@ -175,6 +241,15 @@ fn setup_ui(mut commands: Commands) {
                     TextBundle::from_sections([
                         TextSection::new("Units: ", TextStyle { ..default() }),
                         TextSection::new("0", TextStyle { ..default() }),
+                        TextSection::new("  Supply: ", TextStyle { ..default() }),
+                        TextSection::new("0/0", TextStyle { ..default() }),
+                        TextSection::new(
+                            "",
+                            TextStyle {
+                                color: RED.into(),
+                                ..default()
+                            },
+                        ),
                     ])
                     .with_style(Style {
                         top: Val::Px(20.),
@@ -193,10 +268,16 @@ fn setup_ui(mut commands: Commands) {
             );
             parent
                 .spawn(
-                    TextBundle::from_sections([
-                        TextSection::new("Mineral: ", TextStyle { ..default() }),
-                        TextSection::new("0", TextStyle { ..default() }),
-                    ])
+                    TextBundle::from_sections(ResourceKind::ALL.iter().flat_map(|kind| {
+                        let style = TextStyle {
+                            color: kind.tint(),
+                            ..default()
+                        };
+                        [
+                            TextSection::new(format!("{}: ", kind.label()), style.clone()),
+                            TextSection::new("0  ", style),
+                        ]
+                    }))
                     .with_style(Style {
                         top: Val::Px(20.),
                         left: Val::Px(30.),
@@ -256,6 +337,303 @@ fn setup_ui(mut commands: Commands) {
                 )
                 .insert(WelcomeTextParent);
         });
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 20.0,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                ..default()
+            }),
+        )
+        .insert(UIElement)
+        .insert(SaturationTooltip);
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 20.0,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(20.),
+                top: Val::Px(20.),
+                ..default()
+            }),
+        )
+        .insert(UIElement)
+        .insert(ScoreText);
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 20.0,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(20.),
+                top: Val::Px(180.),
+                ..default()
+            }),
+        )
+        .insert(UIElement)
+        .insert(ObjectivesText);
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(20.),
+                top: Val::Px(70.),
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(UIElement)
+        .insert(ResearchPanel)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Research (P)",
+                TextStyle {
+                    font_size: 18.0,
+                    ..default()
+                },
+            ));
+            for upgrade in Upgrade::ALL {
+                parent
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            width: Val::Px(220.0),
+                            height: Val::Px(50.0),
+                            margin: UiRect::top(Val::Px(6.0)),
+                            border: UiRect::all(Val::Px(2.0)),
+                            padding: UiRect::left(Val::Px(6.0)),
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        border_color: BorderColor(Color::BLACK),
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    })
+                    .insert(ResearchButton(upgrade))
+                    .with_children(|button| {
+                        button
+                            .spawn(TextBundle::from_section(
+                                "",
+                                TextStyle {
+                                    font_size: 14.0,
+                                    ..default()
+                                },
+                            ))
+                            .insert(ResearchButtonText(upgrade));
+                    });
+            }
+        });
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::End,
+                align_items: AlignItems::End,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(UIElement)
+        .with_children(|parent| {
+            parent
+                .spawn(
+                    TextBundle::from_sections([TextSection::new(
+                        "",
+                        TextStyle {
+                            font_size: 18.0,
+                            ..default()
+                        },
+                    )])
+                    .with_style(Style {
+                        right: Val::Px(20.),
+                        bottom: Val::Px(20.),
+                        ..default()
+                    }),
+                )
+                .insert(SelectionPanelText);
+        });
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(UIElement)
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        display: Display::None,
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        top: Val::Px(60.),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .insert(BossHealthPanel)
+                .with_children(|panel| {
+                    panel.spawn(TextBundle::from_section(
+                        "Pirate Capital Ship",
+                        TextStyle {
+                            font_size: 24.0,
+                            color: Color::srgb(1., 0.4, 0.4),
+                            ..default()
+                        },
+                    ));
+                    panel
+                        .spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Px(400.0),
+                                height: Val::Px(16.0),
+                                ..default()
+                            },
+                            background_color: BackgroundColor(Color::srgb(0.2, 0., 0.)),
+                            ..default()
+                        })
+                        .with_children(|bar| {
+                            bar.spawn(NodeBundle {
+                                style: Style {
+                                    width: Val::Percent(100.0),
+                                    height: Val::Percent(100.0),
+                                    ..default()
+                                },
+                                background_color: BackgroundColor(RED.into()),
+                                ..default()
+                            })
+                            .insert(BossHealthFill);
+                        });
+                });
+        });
+}
+
+#[derive(Component)]
+struct BossHealthPanel;
+
+#[derive(Component)]
+struct BossHealthFill;
+
+fn update_boss_health_bar(
+    mut panel_q: Query<&mut Style, (With<BossHealthPanel>, Without<BossHealthFill>)>,
+    mut fill_q: Query<&mut Style, (With<BossHealthFill>, Without<BossHealthPanel>)>,
+    boss_q: Query<&Health, With<Boss>>,
+) {
+    let mut current = 0.;
+    let mut max = 0.;
+    for hp in boss_q.iter() {
+        current += hp.current;
+        max += hp.max_health;
+    }
+    for mut panel_style in panel_q.iter_mut() {
+        panel_style.display = if max > 0. {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+    if max > 0. {
+        for mut fill_style in fill_q.iter_mut() {
+            fill_style.width = Val::Percent(100.0 * current / max);
+        }
+    }
+}
+
+#[derive(Component)]
+struct SelectionPanelText;
+
+const MAX_SELECTION_PANEL_LINES: usize = 12;
+
+#[derive(Component)]
+pub struct SaturationTooltip;
+
+//Shows how many mining slots are taken on the asteroid under the cursor
+fn show_asteroid_saturation(
+    mut tooltip_q: Query<(&mut Text, &mut Style), With<SaturationTooltip>>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    rapier_context: Res<RapierContext>,
+    slots_q: Query<&MiningSlots>,
+) {
+    let (camera, camera_transform) = q_camera.single();
+    let window = q_window.single();
+    let mut saturation = None;
+    let cursor = window.cursor_position();
+    if let Some(world_position) = cursor
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+        .map(|ray| ray.origin.truncate())
+    {
+        rapier_context.intersections_with_point(world_position, QueryFilter::default(), |e| {
+            if let Ok(slots) = slots_q.get(e) {
+                saturation = Some(format!("{}/{}", slots.used(), slots.occupants.len()));
+                return false;
+            }
+            true
+        });
+    }
+    for (mut text, mut style) in tooltip_q.iter_mut() {
+        text.sections[0].value = saturation.clone().unwrap_or_default();
+        if let Some(cursor) = cursor {
+            style.left = Val::Px(cursor.x + 16.);
+            style.top = Val::Px(cursor.y + 16.);
+        }
+    }
+}
+
+fn update_selection_panel(
+    mut panel_text: Query<&mut Text, With<SelectionPanelText>>,
+    currently_selected: Res<CurrentlySelected>,
+    units_q: Query<(&UnitArchetype, Option<&Veterancy>, Option<&MiningComponent>)>,
+) {
+    let mut lines = Vec::new();
+    for e in currently_selected.ent.iter() {
+        if let Ok((archetype, veterancy, mining_comp)) = units_q.get(*e) {
+            let mut line = format!("{:?}", archetype);
+            if let Some(veterancy) = veterancy {
+                line = format!(
+                    "{} {} ({:.0} xp)",
+                    line,
+                    rank_chevrons(veterancy.rank),
+                    veterancy.xp
+                );
+            }
+            if let Some(mining_comp) = mining_comp {
+                if mining_comp.auto_mine {
+                    line += " [auto]";
+                }
+            }
+            lines.push(line);
+        }
+    }
+    if lines.len() > MAX_SELECTION_PANEL_LINES {
+        let hidden = lines.len() - MAX_SELECTION_PANEL_LINES;
+        lines.truncate(MAX_SELECTION_PANEL_LINES);
+        lines.push(format!("+{} more", hidden));
+    }
+    for mut text in panel_text.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
 }
 
 const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
@@ -405,16 +783,241 @@ fn setup_minimap(
                 },
                 ..Default::default()
             });
+            spawn_unit_card(
+                parent,
+                &asset_server,
+                "units/ship_basic.png",
+                Color::srgb(0.6, 0.8, 1.),
+                &format!("T {}", UnitArchetype::Artillery.price().short_label()),
+            );
+            spawn_unit_card(
+                parent,
+                &asset_server,
+                "units/station_A.png",
+                Color::srgb(0.5, 1., 0.6),
+                &format!("G {}", UnitArchetype::Repair.price().short_label()),
+            );
+            for slot in 0..4 {
+                parent
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            width: Val::Px(110.0),
+                            height: Val::Px(60.0),
+                            margin: UiRect::left(Val::Px(10.0)),
+                            border: UiRect::all(Val::Px(3.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        border_color: BorderColor(Color::BLACK),
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    })
+                    .insert(AbilityButton(slot))
+                    .with_children(|button| {
+                        button
+                            .spawn(
+                                TextBundle::from_section(
+                                    "",
+                                    TextStyle {
+                                        font_size: 16.0,
+                                        ..default()
+                                    },
+                                )
+                                .with_text_justify(JustifyText::Center),
+                            )
+                            .insert(AbilityButtonText(slot));
+                    });
+            }
+        });
+}
+
+//HUD buttons for the mothership's abilities
+#[derive(Component)]
+pub struct AbilityButton(usize);
+
+#[derive(Component)]
+struct AbilityButtonText(usize);
+
+fn ability_button_system(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &AbilityButton),
+        Changed<Interaction>,
+    >,
+    mother_unit: Query<(Entity, &Team), With<MotherUnit>>,
+    local_player: Res<LocalPlayer>,
+    mut ev_request: EventWriter<AbilityRequest>,
+) {
+    for (interaction, mut color, ability_button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = PRESSED_BUTTON.into();
+                for (mother_e, team) in mother_unit.iter() {
+                    if team.0 == local_player.0 {
+                        ev_request.send(AbilityRequest {
+                            caster: mother_e,
+                            slot: ability_button.0,
+                            target_pos: None,
+                        });
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON.into();
+            }
+            Interaction::None => {
+                *color = NORMAL_BUTTON.into();
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ResearchPanel;
+
+#[derive(Component)]
+pub struct ResearchButton(Upgrade);
+
+#[derive(Component)]
+struct ResearchButtonText(Upgrade);
+
+fn research_button_system(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &ResearchButton),
+        Changed<Interaction>,
+    >,
+    mut ev_request: EventWriter<ResearchRequest>,
+) {
+    for (interaction, mut color, research_button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = PRESSED_BUTTON.into();
+                ev_request.send(ResearchRequest(research_button.0));
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON.into();
+            }
+            Interaction::None => {
+                *color = NORMAL_BUTTON.into();
+            }
+        }
+    }
+}
+
+fn update_research_panel(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    research: Res<Research>,
+    mut panel_q: Query<&mut Style, With<ResearchPanel>>,
+    mut texts: Query<(&mut Text, &ResearchButtonText)>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyP) {
+        for mut style in panel_q.iter_mut() {
+            if style.display == Display::None {
+                style.display = Display::Flex;
+            } else {
+                style.display = Display::None;
+            }
+        }
+    }
+    for (mut text, button_text) in texts.iter_mut() {
+        let upgrade = button_text.0;
+        let status = if research.completed.contains(&upgrade) {
+            "done".to_string()
+        } else if let Some((_, timer)) = research
+            .current
+            .as_ref()
+            .filter(|(researching, _)| *researching == upgrade)
+        {
+            format!("researching {:.0}s", timer.remaining_secs())
+        } else if research.is_available(upgrade) {
+            upgrade.cost().label()
+        } else {
+            let missing: Vec<&str> = upgrade
+                .prerequisites()
+                .iter()
+                .filter(|p| !research.completed.contains(p))
+                .map(|p| p.name())
+                .collect();
+            format!("needs {}", missing.join(", "))
+        };
+        text.sections[0].value = format!("{}\n{}", upgrade.name(), status);
+    }
+}
+
+fn update_ability_buttons(
+    mut texts: Query<(&mut Text, &AbilityButtonText)>,
+    mother_unit: Query<(&Abilities, &Team), With<MotherUnit>>,
+    local_player: Res<LocalPlayer>,
+) {
+    for (abilities, team) in mother_unit.iter() {
+        if team.0 != local_player.0 {
+            continue;
+        }
+        for (mut text, slot) in texts.iter_mut() {
+            if let Some(ability) = abilities.0.get(slot.0) {
+                let mut status = ability.cost.label();
+                if !ability.cooldown.finished() {
+                    status = format!("{:.0}s", ability.cooldown.remaining_secs());
+                }
+                text.sections[0].value =
+                    format!("{} {}\n{}", ability.hotkey_label(), ability.name, status);
+            }
+        }
+    }
+}
+
+//Same layout as the pre-rendered unit cards, for units that don't have one
+fn spawn_unit_card(
+    parent: &mut ChildBuilder,
+    asset_server: &Res<AssetServer>,
+    icon_path: &str,
+    icon_color: Color,
+    label: &str,
+) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(80.0),
+                height: Val::Px(80.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: BackgroundColor(BLACK.into()),
+            ..Default::default()
+        })
+        .with_children(|card| {
+            card.spawn(ImageBundle {
+                image: UiImage {
+                    texture: asset_server.load(icon_path.to_string()),
+                    color: icon_color,
+                    ..default()
+                },
+                style: Style {
+                    width: Val::Px(55.0),
+                    height: Val::Px(55.0),
+                    ..default()
+                },
+                ..Default::default()
+            });
+            card.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font_size: 18.0,
+                    ..default()
+                },
+            ));
         });
 }
 
-fn setup_menu_ui(mut commands: Commands) {
+fn setup_menu_ui(mut commands: Commands, leaderboard: Res<Leaderboard>) {
     commands
         .spawn(NodeBundle {
             style: Style {
                 width: Val::Percent(100.0),
                 height: Val::Percent(100.0),
-                justify_content: JustifyContent::Center,
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
                 ..default()
             },
             ..default()
@@ -468,41 +1071,399 @@ fn setup_menu_ui(mut commands: Commands) {
                         },
                     ));
                 });
-        });
-}
-
-fn setup_win_screen(mut commands: Commands, minerals: Res<MineralResources>) {
-    let mut win_text = "At least the mothership survived... 
-    But the company expects more from you!";
-    if minerals.mineral >= 200.0 {
-        win_text = "You returned with some minerals
-        But the Company needs more to pay it's shareholders their fair share!"
-    }
-    if minerals.mineral >= 500.0 {
-        win_text = "Some shareholders are happy
-        But we still can't pay our employees..."
-    }
-    if minerals.mineral >= 1000.0 {
-        win_text = "Shareholders are happy,
-        and we could pay the most important employees: CEO, CTO, CFO, COO
-        But we still can't pay the others employees..."
-    }
-    if minerals.mineral >= 2000.0 {
-        win_text = "Solid profits! 
-        We could pay some money to the workers finally!
-        Good job Captain, you earned a day off!";
-    }
-    if minerals.mineral >= 5000.0 {
-        win_text = "Solid profits! 
-        We actualyl didn't expect you to get so much from a single asteroidfield!";
-    }
-    if minerals.mineral >= 10000.0 {
-        win_text ="Okay, you're absolutely amazing!
-        The devs didn't even put a proper text here because this amount of minerals seemed impossible...";
-    }
-
-    commands
-        .spawn(NodeBundle {
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        width: Val::Px(300.0),
+                        height: Val::Px(65.0),
+                        border: UiRect::all(Val::Px(5.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        top: Val::Percent(40.0),
+                        margin: UiRect::top(Val::Px(20.0)),
+                        ..default()
+                    },
+                    border_color: BorderColor(Color::BLACK),
+                    border_radius: BorderRadius::MAX,
+                    background_color: NORMAL_BUTTON.into(),
+                    ..default()
+                })
+                .insert(ButtonInteraction::StartCampaign)
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Campaign",
+                        TextStyle {
+                            font_size: 40.0,
+                            color: Color::srgb(0.9, 0.9, 0.9),
+                            ..default()
+                        },
+                    ));
+                });
+            //Run rules that only add or take away minerals share a row
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        top: Val::Percent(40.0),
+                        margin: UiRect::top(Val::Px(20.0)),
+                        column_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|row| {
+                    row.spawn(ButtonBundle {
+                        style: Style {
+                            width: Val::Px(300.0),
+                            height: Val::Px(65.0),
+                            border: UiRect::all(Val::Px(5.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        border_color: BorderColor(Color::BLACK),
+                        border_radius: BorderRadius::MAX,
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    })
+                    .insert(ButtonInteraction::ToggleQuota)
+                    .with_children(|parent| {
+                        parent
+                            .spawn(TextBundle::from_section(
+                                "",
+                                TextStyle {
+                                    font_size: 30.0,
+                                    color: Color::srgb(0.9, 0.9, 0.9),
+                                    ..default()
+                                },
+                            ))
+                            .insert(MenuOptionText::Quota);
+                    });
+                    row.spawn(ButtonBundle {
+                        style: Style {
+                            width: Val::Px(300.0),
+                            height: Val::Px(65.0),
+                            border: UiRect::all(Val::Px(5.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        border_color: BorderColor(Color::BLACK),
+                        border_radius: BorderRadius::MAX,
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    })
+                    .insert(ButtonInteraction::ToggleUpkeep)
+                    .with_children(|parent| {
+                        parent
+                            .spawn(TextBundle::from_section(
+                                "",
+                                TextStyle {
+                                    font_size: 30.0,
+                                    color: Color::srgb(0.9, 0.9, 0.9),
+                                    ..default()
+                                },
+                            ))
+                            .insert(MenuOptionText::Upkeep);
+                    });
+                });
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        width: Val::Px(300.0),
+                        height: Val::Px(65.0),
+                        border: UiRect::all(Val::Px(5.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        top: Val::Percent(40.0),
+                        margin: UiRect::top(Val::Px(20.0)),
+                        ..default()
+                    },
+                    border_color: BorderColor(Color::BLACK),
+                    border_radius: BorderRadius::MAX,
+                    background_color: NORMAL_BUTTON.into(),
+                    ..default()
+                })
+                .insert(ButtonInteraction::CycleDifficulty)
+                .with_children(|parent| {
+                    parent
+                        .spawn(TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 30.0,
+                                color: Color::srgb(0.9, 0.9, 0.9),
+                                ..default()
+                            },
+                        ))
+                        .insert(MenuOptionText::Difficulty);
+                });
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        width: Val::Px(300.0),
+                        height: Val::Px(65.0),
+                        border: UiRect::all(Val::Px(5.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        top: Val::Percent(40.0),
+                        margin: UiRect::top(Val::Px(20.0)),
+                        ..default()
+                    },
+                    border_color: BorderColor(Color::BLACK),
+                    border_radius: BorderRadius::MAX,
+                    background_color: NORMAL_BUTTON.into(),
+                    ..default()
+                })
+                .insert(ButtonInteraction::CycleMode)
+                .with_children(|parent| {
+                    parent
+                        .spawn(TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 30.0,
+                                color: Color::srgb(0.9, 0.9, 0.9),
+                                ..default()
+                            },
+                        ))
+                        .insert(MenuOptionText::Mode);
+                });
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        width: Val::Px(300.0),
+                        height: Val::Px(65.0),
+                        border: UiRect::all(Val::Px(5.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        top: Val::Percent(40.0),
+                        margin: UiRect::top(Val::Px(20.0)),
+                        ..default()
+                    },
+                    border_color: BorderColor(Color::BLACK),
+                    border_radius: BorderRadius::MAX,
+                    background_color: NORMAL_BUTTON.into(),
+                    ..default()
+                })
+                .insert(ButtonInteraction::CycleScenario)
+                .with_children(|parent| {
+                    parent
+                        .spawn(TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 30.0,
+                                color: Color::srgb(0.9, 0.9, 0.9),
+                                ..default()
+                            },
+                        ))
+                        .insert(MenuOptionText::Scenario);
+                });
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        width: Val::Px(300.0),
+                        height: Val::Px(65.0),
+                        border: UiRect::all(Val::Px(5.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        top: Val::Percent(40.0),
+                        margin: UiRect::top(Val::Px(20.0)),
+                        ..default()
+                    },
+                    border_color: BorderColor(Color::BLACK),
+                    border_radius: BorderRadius::MAX,
+                    background_color: NORMAL_BUTTON.into(),
+                    ..default()
+                })
+                .insert(ButtonInteraction::ShowLeaderboard)
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "High scores",
+                        TextStyle {
+                            font_size: 30.0,
+                            color: Color::srgb(0.9, 0.9, 0.9),
+                            ..default()
+                        },
+                    ));
+                });
+        });
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                left: Val::Px(40.),
+                top: Val::Px(180.),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(10.)),
+                ..default()
+            },
+            background_color: BackgroundColor(Color::srgba(0., 0., 0., 0.8)),
+            ..default()
+        })
+        .insert(UIElement)
+        .insert(LeaderboardPanel)
+        .with_children(|parent| {
+            let tables = leaderboard.tables();
+            if tables.is_empty() {
+                parent.spawn(TextBundle::from_section(
+                    "No runs recorded yet",
+                    TextStyle {
+                        font_size: 20.0,
+                        ..default()
+                    },
+                ));
+            }
+            for (table_name, rows) in tables {
+                parent.spawn(
+                    TextBundle::from_section(
+                        table_name,
+                        TextStyle {
+                            font_size: 26.0,
+                            color: YELLOW.into(),
+                            ..default()
+                        },
+                    )
+                    .with_style(Style {
+                        margin: UiRect::top(Val::Px(10.)),
+                        ..default()
+                    }),
+                );
+                for (rank, entry) in rows.iter().enumerate() {
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                align_items: AlignItems::Center,
+                                column_gap: Val::Px(10.),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|row| {
+                            row.spawn(TextBundle::from_section(
+                                format!(
+                                    "{}. {:.0}  {:.0} minerals  {} waves  {}",
+                                    rank + 1,
+                                    entry.score,
+                                    entry.minerals,
+                                    entry.waves,
+                                    entry.date
+                                ),
+                                TextStyle {
+                                    font_size: 20.0,
+                                    ..default()
+                                },
+                            ));
+                            //Clicking the seed replays the same map
+                            row.spawn(ButtonBundle {
+                                style: Style {
+                                    padding: UiRect::horizontal(Val::Px(6.)),
+                                    ..default()
+                                },
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            })
+                            .insert(ButtonInteraction::ReplaySeed {
+                                seed: entry.seed,
+                                mode: entry.mode,
+                                scenario: entry.scenario,
+                                difficulty: entry.difficulty,
+                            })
+                            .with_children(|button| {
+                                button.spawn(TextBundle::from_section(
+                                    format!("seed {}", entry.seed),
+                                    TextStyle {
+                                        font_size: 20.0,
+                                        color: Color::srgb(0.5, 0.8, 1.),
+                                        ..default()
+                                    },
+                                ));
+                            });
+                        });
+                }
+            }
+        });
+}
+
+#[derive(Component)]
+struct LeaderboardPanel;
+
+//Menu buttons that show the option they change
+#[derive(Component)]
+enum MenuOptionText {
+    Quota,
+    Upkeep,
+    Difficulty,
+    Mode,
+    Scenario,
+}
+
+fn update_menu_options(
+    quota: Res<ExtractionQuota>,
+    upkeep: Res<FleetUpkeep>,
+    difficulty: Res<Difficulty>,
+    game_mode: Res<GameMode>,
+    scenario: Res<Scenario>,
+    mut texts: Query<(&mut Text, &MenuOptionText)>,
+) {
+    for (mut text, option) in texts.iter_mut() {
+        text.sections[0].value = match option {
+            MenuOptionText::Quota if quota.enabled => format!("Quota: {:.0}", quota.amount),
+            MenuOptionText::Quota => "Quota: off".to_string(),
+            MenuOptionText::Upkeep if upkeep.enabled => "Upkeep: on".to_string(),
+            MenuOptionText::Upkeep => "Upkeep: off".to_string(),
+            MenuOptionText::Difficulty => format!("Difficulty: {}", difficulty.label()),
+            MenuOptionText::Mode => format!("Mode: {}", game_mode.label()),
+            MenuOptionText::Scenario => format!("Scenario: {}", scenario.label()),
+        };
+    }
+}
+
+fn setup_win_screen(
+    mut commands: Commands,
+    ledger: Res<ResourceLedger>,
+    score: Res<Score>,
+    campaign: Res<Campaign>,
+) {
+    let mut title = "Mothertship escaped";
+    if campaign.is_finished() {
+        title = "Campaign complete!";
+    }
+    let (next_button, next_label) = if campaign.active {
+        (ButtonInteraction::ContinueCampaign, "Continue")
+    } else {
+        (ButtonInteraction::BackToMenu, "Back to Menu")
+    };
+    //Judged on what was mined this run, not what is left in the bank
+    let mut win_text = "At least the mothership survived... 
+    But the company expects more from you!";
+    if score.minerals >= 200.0 {
+        win_text = "You returned with some minerals
+        But the Company needs more to pay it's shareholders their fair share!"
+    }
+    if score.minerals >= 500.0 {
+        win_text = "Some shareholders are happy
+        But we still can't pay our employees..."
+    }
+    if score.minerals >= 1000.0 {
+        win_text = "Shareholders are happy,
+        and we could pay the most important employees: CEO, CTO, CFO, COO
+        But we still can't pay the others employees..."
+    }
+    if score.minerals >= 2000.0 {
+        win_text = "Solid profits! 
+        We could pay some money to the workers finally!
+        Good job Captain, you earned a day off!";
+    }
+    if score.minerals >= 5000.0 {
+        win_text = "Solid profits! 
+        We actualyl didn't expect you to get so much from a single asteroidfield!";
+    }
+    if score.minerals >= 10000.0 {
+        win_text ="Okay, you're absolutely amazing!
+        The devs didn't even put a proper text here because this amount of minerals seemed impossible...";
+    }
+
+    commands
+        .spawn(NodeBundle {
             style: Style {
                 width: Val::Percent(100.0),
                 height: Val::Percent(100.0),
@@ -518,7 +1479,7 @@ fn setup_win_screen(mut commands: Commands, minerals: Res<MineralResources>) {
         .with_children(|parent| {
             parent.spawn(
                 TextBundle::from_sections([TextSection::new(
-                    "Mothertship escaped",
+                    title,
                     TextStyle {
                         font_size: 100.0,
                         ..default()
@@ -532,7 +1493,10 @@ fn setup_win_screen(mut commands: Commands, minerals: Res<MineralResources>) {
             );
             parent.spawn(
                 TextBundle::from_sections([TextSection::new(
-                    format!("you got: {} minerals", minerals.mineral),
+                    format!(
+                        "you got: {:.0} ore, {:.0} ice, {:.0} crystal",
+                        ledger.ore, ledger.ice, ledger.crystal
+                    ),
                     TextStyle {
                         font_size: 70.0,
                         ..default()
@@ -544,6 +1508,37 @@ fn setup_win_screen(mut commands: Commands, minerals: Res<MineralResources>) {
                     ..default()
                 }),
             );
+            parent.spawn(
+                TextBundle::from_sections([
+                    TextSection::new(
+                        format!("Grade {}  ", score.grade()),
+                        TextStyle {
+                            font_size: 70.0,
+                            color: YELLOW.into(),
+                            ..default()
+                        },
+                    ),
+                    TextSection::new(
+                        format!(
+                            "score {:.0} = {:.0} minerals + {:.0} fleet + {:.0} waves + {:.0} time",
+                            score.total(),
+                            score.minerals,
+                            score.fleet,
+                            score.wave_points(),
+                            score.time_bonus()
+                        ),
+                        TextStyle {
+                            font_size: 40.0,
+                            ..default()
+                        },
+                    ),
+                ])
+                .with_text_justify(JustifyText::Center)
+                .with_style(Style {
+                    top: Val::Percent(5.),
+                    ..default()
+                }),
+            );
             parent.spawn(
                 TextBundle::from_sections([TextSection::new(
                     win_text,
@@ -578,10 +1573,10 @@ fn setup_win_screen(mut commands: Commands, minerals: Res<MineralResources>) {
                     background_color: NORMAL_BUTTON.into(),
                     ..default()
                 })
-                .insert(ButtonInteraction::BackToMenu)
+                .insert(next_button)
                 .with_children(|parent| {
                     parent.spawn(TextBundle::from_section(
-                        "Back to Menu",
+                        next_label,
                         TextStyle {
                             font_size: 40.0,
                             color: Color::srgb(0.9, 0.9, 0.9),
@@ -668,6 +1663,215 @@ fn setup_lose_screen(mut commands: Commands) {
         });
 }
 
+//Endless runs end here, the only question is how long the fleet held out
+fn setup_survival_screen(mut commands: Commands, score: Res<Score>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::SpaceBetween,
+                flex_direction: FlexDirection::Column,
+                align_content: AlignContent::SpaceEvenly,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(UIElement)
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_sections([
+                    TextSection::new(
+                        "Mothership lost!\n",
+                        TextStyle {
+                            font_size: 100.0,
+                            ..default()
+                        },
+                    ),
+                    TextSection::new(
+                        format!(
+                            "The fleet held out for {} and reached wave {}",
+                            score.survival_time(),
+                            score.wave_reached
+                        ),
+                        TextStyle {
+                            font_size: 70.0,
+                            ..default()
+                        },
+                    ),
+                ])
+                .with_text_justify(JustifyText::Center)
+                .with_style(Style {
+                    top: Val::Percent(5.),
+                    width: Val::Percent(80.0),
+                    ..default()
+                }),
+            );
+
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        width: Val::Px(300.0),
+                        height: Val::Px(65.0),
+                        border: UiRect::all(Val::Px(5.0)),
+                        // horizontally center child text
+                        justify_content: JustifyContent::Center,
+                        // vertically center child text
+                        align_items: AlignItems::Center,
+                        align_content: AlignContent::Center,
+                        justify_self: JustifySelf::Center,
+                        bottom: Val::Percent(5.0),
+                        ..default()
+                    },
+                    border_color: BorderColor(Color::BLACK),
+                    border_radius: BorderRadius::MAX,
+                    background_color: NORMAL_BUTTON.into(),
+                    ..default()
+                })
+                .insert(ButtonInteraction::BackToMenu)
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Back to Menu",
+                        TextStyle {
+                            font_size: 40.0,
+                            color: Color::srgb(0.9, 0.9, 0.9),
+                            ..default()
+                        },
+                    ));
+                });
+        });
+}
+
+fn setup_fleet_screen(mut commands: Commands, campaign: Res<Campaign>) {
+    let mut fleet_lines: Vec<String> = Vec::new();
+    for archetype in [
+        UnitArchetype::Miner,
+        UnitArchetype::Melee,
+        UnitArchetype::Ranged,
+        UnitArchetype::Artillery,
+        UnitArchetype::Repair,
+    ] {
+        let ships: Vec<_> = campaign
+            .fleet
+            .iter()
+            .filter(|ship| ship.archetype == archetype)
+            .collect();
+        if ships.is_empty() {
+            continue;
+        }
+        let xp: f32 = ships.iter().map(|ship| ship.xp).sum();
+        fleet_lines.push(format!("{}x {:?} ({:.0} xp)", ships.len(), archetype, xp));
+    }
+    if fleet_lines.is_empty() {
+        fleet_lines.push("Only the mothership".to_string());
+    }
+    let mut upgrades: Vec<&str> = Upgrade::ALL
+        .iter()
+        .filter(|upgrade| campaign.upgrades.contains(upgrade))
+        .map(|upgrade| upgrade.name())
+        .collect();
+    if upgrades.is_empty() {
+        upgrades.push("none");
+    }
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::SpaceBetween,
+                flex_direction: FlexDirection::Column,
+                align_content: AlignContent::SpaceEvenly,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(UIElement)
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_sections([
+                    TextSection::new(
+                        format!(
+                            "Mission {}/{}: {}\n",
+                            campaign.mission + 1,
+                            CAMPAIGN_MISSIONS.len(),
+                            campaign.current_scenario().label()
+                        ),
+                        TextStyle {
+                            font_size: 70.0,
+                            ..default()
+                        },
+                    ),
+                    TextSection::new(
+                        format!(
+                            "Banked: {:.0} ore, {:.0} ice, {:.0} crystal\nUpgrades: {}\n\nFleet\n{}",
+                            campaign.ledger.ore,
+                            campaign.ledger.ice,
+                            campaign.ledger.crystal,
+                            upgrades.join(", "),
+                            fleet_lines.join("\n")
+                        ),
+                        TextStyle {
+                            font_size: 35.0,
+                            ..default()
+                        },
+                    ),
+                ])
+                .with_text_justify(JustifyText::Center)
+                .with_style(Style {
+                    top: Val::Percent(5.),
+                    width: Val::Percent(80.0),
+                    ..default()
+                }),
+            );
+            for (action, label) in [
+                (ButtonInteraction::LaunchMission, "Launch mission"),
+                (ButtonInteraction::BackToMenu, "Abandon campaign"),
+            ] {
+                parent
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            width: Val::Px(300.0),
+                            height: Val::Px(65.0),
+                            border: UiRect::all(Val::Px(5.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            bottom: Val::Percent(5.0),
+                            ..default()
+                        },
+                        border_color: BorderColor(Color::BLACK),
+                        border_radius: BorderRadius::MAX,
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    })
+                    .insert(action)
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            label,
+                            TextStyle {
+                                font_size: 35.0,
+                                color: Color::srgb(0.9, 0.9, 0.9),
+                                ..default()
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+//Everything the main menu buttons can change for the next run
+#[derive(SystemParam)]
+struct MenuSettings<'w> {
+    quota: ResMut<'w, ExtractionQuota>,
+    upkeep: ResMut<'w, FleetUpkeep>,
+    map_seed: ResMut<'w, MapSeed>,
+    difficulty: ResMut<'w, Difficulty>,
+    game_mode: ResMut<'w, GameMode>,
+    scenario: ResMut<'w, Scenario>,
+}
+
 fn button_system(
     mut interaction_query: Query<
         (
@@ -679,7 +1883,18 @@ fn button_system(
         (Changed<Interaction>, With<Button>),
     >,
     mut app_state: ResMut<NextState<AppState>>,
+    settings: MenuSettings,
+    mut campaign: ResMut<Campaign>,
+    mut leaderboard_panel: Query<&mut Style, With<LeaderboardPanel>>,
 ) {
+    let MenuSettings {
+        mut quota,
+        mut upkeep,
+        mut map_seed,
+        mut difficulty,
+        mut game_mode,
+        mut scenario,
+    } = settings;
     for (interaction, mut color, mut border_color, button_interaction) in &mut interaction_query {
         match *interaction {
             Interaction::Pressed => {
@@ -687,11 +1902,66 @@ fn button_system(
                 border_color.0 = RED.into();
                 match button_interaction {
                     ButtonInteraction::StartGame => {
+                        *campaign = Campaign::default();
+                        app_state.set(AppState::InGame);
+                    }
+                    ButtonInteraction::StartCampaign => {
+                        *campaign = Campaign::start();
+                        app_state.set(AppState::FleetScreen);
+                    }
+                    ButtonInteraction::ContinueCampaign => {
+                        app_state.set(AppState::FleetScreen);
+                    }
+                    ButtonInteraction::LaunchMission => {
+                        if campaign.menu_choice.is_none() {
+                            campaign.menu_choice = Some((*game_mode, *scenario));
+                        }
+                        *scenario = campaign.current_scenario();
+                        *game_mode = GameMode::Extraction;
+                        campaign.pending_restore = true;
                         app_state.set(AppState::InGame);
                     }
                     ButtonInteraction::BackToMenu => {
                         app_state.set(AppState::Menu);
                     }
+                    ButtonInteraction::ToggleQuota => {
+                        quota.enabled = !quota.enabled;
+                    }
+                    ButtonInteraction::ToggleUpkeep => {
+                        upkeep.enabled = !upkeep.enabled;
+                    }
+                    ButtonInteraction::CycleDifficulty => {
+                        *difficulty = difficulty.next();
+                    }
+                    ButtonInteraction::CycleMode => {
+                        *game_mode = game_mode.next();
+                    }
+                    ButtonInteraction::CycleScenario => {
+                        *scenario = scenario.next();
+                    }
+                    ButtonInteraction::ShowLeaderboard => {
+                        for mut style in leaderboard_panel.iter_mut() {
+                            if style.display == Display::None {
+                                style.display = Display::Flex;
+                            } else {
+                                style.display = Display::None;
+                            }
+                        }
+                    }
+                    ButtonInteraction::ReplaySeed {
+                        seed,
+                        mode,
+                        scenario: replay_scenario,
+                        difficulty: replay_difficulty,
+                    } => {
+                        //The same seed only gives the same map with the settings it was played on
+                        *campaign = Campaign::default();
+                        map_seed.replay = Some(*seed);
+                        *game_mode = *mode;
+                        *scenario = *replay_scenario;
+                        *difficulty = *replay_difficulty;
+                        app_state.set(AppState::InGame);
+                    }
                 }
                 //TODO: handle each button here?? with a marker component
             }
@@ -720,6 +1990,21 @@ fn destroy_all_ui(mut commands: Commands, all_ui: Query<Entity, With<UIElement>>
 pub enum ButtonInteraction {
     StartGame,
     BackToMenu,
+    StartCampaign,
+    ContinueCampaign,
+    LaunchMission,
+    ToggleQuota,
+    ToggleUpkeep,
+    CycleDifficulty,
+    CycleMode,
+    CycleScenario,
+    ShowLeaderboard,
+    ReplaySeed {
+        seed: u64,
+        mode: GameMode,
+        scenario: Scenario,
+        difficulty: Difficulty,
+    },
 }
 
 #[derive(Component)]
@@ -748,8 +2033,23 @@ pub fn run_down_welcome_text(
         }
     }
 }
-pub fn reset_welcome_text(mut welcome_txt: ResMut<WelcomeText>) {
+pub fn reset_welcome_text(
+    mut welcome_txt: ResMut<WelcomeText>,
+    difficulty: Res<Difficulty>,
+    game_mode: Res<GameMode>,
+) {
     *welcome_txt = WelcomeText::default();
+    if *game_mode == GameMode::Endless {
+        welcome_txt.whole_text = welcome_txt.whole_text.replace(
+            "We'll be giving you an extraction location in 5 minutes\nMake sure you're all loaded up with mineral when you request extraction",
+            "There is no extraction this time\nThe pirates keep coming, hold out as long as you can",
+        );
+        return;
+    }
+    welcome_txt.whole_text = welcome_txt.whole_text.replace(
+        "in 5 minutes",
+        &format!("in {:.0} minutes", difficulty.extraction_time() / 60.),
+    );
 }
 
 #[derive(Resource)]
@@ -786,8 +2086,76 @@ Good luck Captain...."#,
 fn show_extraction_timer(
     mut extraction_timer_text: Query<&mut Text, With<ExtractionTimer>>,
     time_left: Res<EndGameTimer>,
+    game_mode: Res<GameMode>,
+    score: Res<Score>,
 ) {
     for mut txt in extraction_timer_text.iter_mut() {
-        txt.sections[1].value = format!("{:.0}", time_left.0.remaining_secs());
+        match *game_mode {
+            GameMode::Extraction => {
+                txt.sections[1].value = format!("{:.0}", time_left.0.remaining_secs());
+            }
+            GameMode::Endless => {
+                txt.sections[0].value = "Survived: ".to_string();
+                txt.sections[1].value = score.survival_time();
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+struct ScoreText;
+
+fn update_score_text(
+    mut score_text: Query<&mut Text, With<ScoreText>>,
+    score: Res<Score>,
+    game_mode: Res<GameMode>,
+) {
+    for mut txt in score_text.iter_mut() {
+        if *game_mode == GameMode::Endless {
+            txt.sections[0].value = format!(
+                "Survived {}\nWave {}\nMinerals {:.0}",
+                score.survival_time(),
+                score.wave_reached,
+                score.minerals
+            );
+            continue;
+        }
+        txt.sections[0].value = format!(
+            "Score {:.0} ({})\nMinerals {:.0}\nFleet {:.0}\nWaves {} ({:.0})\nTime bonus {:.0}",
+            score.total(),
+            score.grade(),
+            score.minerals,
+            score.fleet,
+            score.waves_defeated,
+            score.wave_points(),
+            score.time_bonus()
+        );
+    }
+}
+
+#[derive(Component)]
+struct ObjectivesText;
+
+fn update_objectives_text(
+    mut objectives_text: Query<&mut Text, With<ObjectivesText>>,
+    objectives: Res<Objectives>,
+) {
+    for mut txt in objectives_text.iter_mut() {
+        let mut value = String::new();
+        if !objectives.0.is_empty() {
+            value += "Objectives";
+        }
+        for objective in objectives.0.iter() {
+            let mark = match objective.status {
+                ObjectiveStatus::Active => "[ ]",
+                ObjectiveStatus::Completed => "[x]",
+                ObjectiveStatus::Failed => "[!]",
+            };
+            value += &format!("\n{} {}", mark, objective.name);
+            if !objective.progress.is_empty() {
+                value += &format!(" ({})", objective.progress);
+            }
+        }
+        txt.sections[0].value = value;
     }
 }
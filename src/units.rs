@@ -1,18 +1,26 @@
-use crate::materials::{Mineable, MineralResources};
-use crate::movement::{Avoidance, FaceMovementDirection};
+use crate::abilities::{Abilities, Ability, AbilityKind};
+use crate::difficulty::Difficulty;
+use crate::diplomacy::{Diplomacy, LocalPlayer, Relation};
+use crate::materials::{
+    Asteroid, Mineable, MiningSlots, ResourceCost, ResourceKind, ResourceLedger,
+};
+use crate::movement::{Avoidance, FaceMovementDirection, MotionTracker};
 use crate::selection::{CurrentlySelected, Selectable, Team};
+use crate::structures::{PlacementMode, Structure, UnderConstruction, BUILD_RANGE};
 use crate::ui::{spawn_build_order_card, BuildQueueParent};
 use crate::AppState;
 use crate::DontDestroyOnLoad;
 use crate::GamePhase;
 use crate::MainCamera;
 use crate::MapBoundaries;
+use crate::MapRng;
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 use bevy::render::view::visibility::RenderLayers;
 use bevy::window::PrimaryWindow;
 use bevy_rapier2d::prelude::*;
 use rand::Rng;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::f32::consts::FRAC_PI_2;
 
 pub struct UnitsPlugin;
@@ -20,7 +28,10 @@ pub struct UnitsPlugin;
 impl Plugin for UnitsPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, spawn_command_highlighters); //Temp
-        app.add_systems(OnEnter(AppState::InGame), (spawn_units, reset_mastermind));
+        app.add_systems(
+            OnEnter(AppState::InGame),
+            (spawn_units, reset_mastermind, reset_kill_stats),
+        );
         app.add_systems(OnEnter(AppState::Menu), spawn_main_menu_units);
         app.add_systems(
             Update,
@@ -29,14 +40,32 @@ impl Plugin for UnitsPlugin {
                 move_units,
                 bullet_behaviour,
                 tick_attack_timers,
+                regenerate_shields,
+                fade_explosions,
                 handle_aggressive_pigs,
                 handle_mildly_aggressive_pigs,
                 enemy_mastermind,
                 handle_add_to_build_queue,
                 build_requested_units,
+                repair_units,
+                toggle_automation,
+                auto_retarget_miners,
+                seek_free_slots,
+                count_supply,
+                pay_upkeep,
             )
                 .run_if(in_state(GamePhase::Playing)), //TODO: ONLY RUN THESE SYSTEMS IF APPSTATE == INGAME
         );
+        app.add_systems(
+            Update,
+            (
+                fire_hardpoints,
+                use_emp_abilities,
+                launch_drones,
+                tick_stuns,
+            )
+                .run_if(in_state(GamePhase::Playing)),
+        );
         app.add_systems(
             PostUpdate,
             (
@@ -44,27 +73,117 @@ impl Plugin for UnitsPlugin {
                 update_health_bars,
                 process_damage_events,
                 check_dead_units,
+                explode_destroyed_units,
+                count_kills,
+                cleanup_dead_references,
+                award_kill_xp,
+                rank_up_units,
+                pay_boss_bounty,
             ),
         );
         app.add_event::<DamageEvent>();
+        app.add_event::<UnitDestroyed>();
+        app.init_resource::<KillStats>();
         app.init_resource::<EnemyBrain>();
         app.init_resource::<BuildQueue>();
+        app.init_resource::<Supply>();
+        app.init_resource::<FleetUpkeep>();
     }
 }
 
 #[derive(Component)]
 pub struct MotherUnit;
 
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum UnitArchetype {
+    Mothership,
+    Miner,
+    Melee,
+    Ranged,
+    Artillery,
+    Repair,
+    PirateMelee,
+    PirateRanged,
+    PirateBomber,
+    PirateBoss,
+    DefenseTurret,
+    MiningOutpost,
+    SensorBeacon,
+    CargoDepot,
+    SupplyDepot,
+}
+
+impl UnitArchetype {
+    //Price of the unit per resource kind, pirates are priced as if they were built by the player
+    pub fn price(&self) -> ResourceCost {
+        match self {
+            UnitArchetype::Mothership => ResourceCost::new(300., 0., 0.),
+            UnitArchetype::Miner => ResourceCost::new(10., 0., 0.),
+            UnitArchetype::Melee | UnitArchetype::PirateMelee => ResourceCost::new(30., 0., 0.),
+            UnitArchetype::Ranged | UnitArchetype::PirateRanged => ResourceCost::new(60., 0., 0.),
+            UnitArchetype::Artillery | UnitArchetype::PirateBomber => {
+                ResourceCost::new(50., 0., 30.)
+            }
+            UnitArchetype::Repair => ResourceCost::new(30., 20., 0.),
+            UnitArchetype::PirateBoss => ResourceCost::new(300., 0., 200.),
+            UnitArchetype::DefenseTurret => ResourceCost::new(45., 0., 15.),
+            UnitArchetype::MiningOutpost => ResourceCost::new(60., 20., 0.),
+            UnitArchetype::SensorBeacon => ResourceCost::new(20., 0., 20.),
+            UnitArchetype::CargoDepot => ResourceCost::new(50., 0., 0.),
+            UnitArchetype::SupplyDepot => ResourceCost::new(40., 10., 0.),
+        }
+    }
+
+    //Supply the unit takes up, structures and the mothership are free
+    pub fn supply(&self) -> i32 {
+        match self {
+            UnitArchetype::Miner => 1,
+            UnitArchetype::Melee | UnitArchetype::PirateMelee => 2,
+            UnitArchetype::Ranged | UnitArchetype::PirateRanged => 3,
+            UnitArchetype::Artillery | UnitArchetype::PirateBomber => 4,
+            UnitArchetype::Repair => 2,
+            _ => 0,
+        }
+    }
+
+    //Overall value of the unit, used for experience and salvage
+    pub fn cost(&self) -> f32 {
+        self.price().total()
+    }
+}
+
+#[derive(Event)]
+pub struct UnitDestroyed {
+    pub entity: Entity,
+    pub team: Option<i32>,
+    pub killer: Option<Entity>,
+    pub position: Vec3,
+    pub archetype: Option<UnitArchetype>,
+    pub drone: bool,
+}
+
+//Whoever dealt the last hit, so kills can be credited
+#[derive(Component)]
+pub struct LastDamagedBy(pub Entity);
+
+#[derive(Resource, Default)]
+pub struct KillStats {
+    pub kills_by_team: HashMap<i32, u32>,
+    pub losses_by_team: HashMap<i32, u32>,
+}
+
 #[derive(Resource)]
 pub struct EnemyBrain {
     pub current_wave: i32,
     pub time_between_wave: Timer,
+    pub boss_wave_interval: i32,
 }
 impl Default for EnemyBrain {
     fn default() -> EnemyBrain {
         EnemyBrain {
             current_wave: 0,
             time_between_wave: Timer::from_seconds(20.0, TimerMode::Once),
+            boss_wave_interval: 5,
         }
     }
 }
@@ -73,6 +192,102 @@ pub enum BuildOrder {
     Miner(Entity),
     Melee(Entity),
     Ranged(Entity),
+    Artillery(Entity),
+    Repair(Entity),
+}
+
+impl BuildOrder {
+    pub fn archetype(&self) -> UnitArchetype {
+        match self {
+            BuildOrder::Miner(_) => UnitArchetype::Miner,
+            BuildOrder::Melee(_) => UnitArchetype::Melee,
+            BuildOrder::Ranged(_) => UnitArchetype::Ranged,
+            BuildOrder::Artillery(_) => UnitArchetype::Artillery,
+            BuildOrder::Repair(_) => UnitArchetype::Repair,
+        }
+    }
+}
+
+//Gives the owning team room for more ships
+#[derive(Component)]
+pub struct SupplyProvider(pub i32);
+
+//Ships carried over from an earlier campaign mission, they take no supply and pay no upkeep
+#[derive(Component)]
+pub struct SupplyExempt;
+
+//Fleets bigger than this pay ore every second for each supply above it
+const UPKEEP_FREE_SUPPLY: i32 = 20;
+const UPKEEP_PER_SUPPLY: f32 = 0.1;
+
+#[derive(Resource)]
+pub struct Supply {
+    pub used: i32,
+    pub cap: i32,
+    //Set while the next unit in the build queue doesn't fit
+    pub blocked: bool,
+    pub upkeep_timer: Timer,
+}
+impl Default for Supply {
+    fn default() -> Supply {
+        Supply {
+            used: 0,
+            cap: 0,
+            blocked: false,
+            upkeep_timer: Timer::from_seconds(1.0, TimerMode::Repeating),
+        }
+    }
+}
+
+//Toggled from the main menu, outlives the per-run Supply reset
+#[derive(Resource)]
+pub struct FleetUpkeep {
+    pub enabled: bool,
+}
+
+impl Default for FleetUpkeep {
+    fn default() -> FleetUpkeep {
+        FleetUpkeep { enabled: true }
+    }
+}
+
+impl Supply {
+    pub fn upkeep_per_second(&self, upkeep: &FleetUpkeep) -> f32 {
+        if !upkeep.enabled {
+            return 0.;
+        }
+        (self.used - UPKEEP_FREE_SUPPLY).max(0) as f32 * UPKEEP_PER_SUPPLY
+    }
+}
+
+fn count_supply(
+    mut supply: ResMut<Supply>,
+    units: Query<(&UnitArchetype, &Team), Without<SupplyExempt>>,
+    providers: Query<(&SupplyProvider, &Team), Without<UnderConstruction>>,
+    local_player: Res<LocalPlayer>,
+) {
+    supply.used = units
+        .iter()
+        .filter(|(_, team)| team.0 == local_player.0)
+        .map(|(archetype, _)| archetype.supply())
+        .sum();
+    supply.cap = providers
+        .iter()
+        .filter(|(_, team)| team.0 == local_player.0)
+        .map(|(provider, _)| provider.0)
+        .sum();
+}
+
+fn pay_upkeep(
+    time: Res<Time>,
+    mut supply: ResMut<Supply>,
+    upkeep: Res<FleetUpkeep>,
+    mut ledger: ResMut<ResourceLedger>,
+) {
+    supply.upkeep_timer.tick(time.delta());
+    if supply.upkeep_timer.just_finished() {
+        ledger.ore = (ledger.ore - supply.upkeep_per_second(&upkeep)).max(0.);
+    }
 }
 
 #[derive(Resource)]
@@ -94,37 +309,60 @@ impl Default for BuildQueue {
 fn handle_add_to_build_queue(
     mut build_queue: ResMut<BuildQueue>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut minerals: ResMut<MineralResources>,
+    mut ledger: ResMut<ResourceLedger>,
     mut commands: Commands,
     query_of_card_parent: Query<Entity, With<BuildQueueParent>>,
     asset_server: Res<AssetServer>,
 ) {
     for card_parent in query_of_card_parent.iter() {
         if build_queue.queue.len() < build_queue.max_request {
-            if keyboard_input.just_pressed(KeyCode::KeyQ) && minerals.mineral >= 10.0 {
-                minerals.mineral -= 10.0;
+            if keyboard_input.just_pressed(KeyCode::KeyQ)
+                && ledger.spend(&UnitArchetype::Miner.price())
+            {
                 if let Some(card_entity) =
                     spawn_build_order_card(&mut commands, card_parent, &asset_server, 0)
                 {
                     build_queue.queue.push_back(BuildOrder::Miner(card_entity));
                 }
             }
-            if keyboard_input.just_pressed(KeyCode::KeyE) && minerals.mineral >= 30.0 {
-                minerals.mineral -= 30.0;
+            if keyboard_input.just_pressed(KeyCode::KeyE)
+                && ledger.spend(&UnitArchetype::Melee.price())
+            {
                 if let Some(card_entity) =
                     spawn_build_order_card(&mut commands, card_parent, &asset_server, 1)
                 {
                     build_queue.queue.push_back(BuildOrder::Melee(card_entity));
                 }
             }
-            if keyboard_input.just_pressed(KeyCode::KeyR) && minerals.mineral >= 60.0 {
-                minerals.mineral -= 60.0;
+            if keyboard_input.just_pressed(KeyCode::KeyR)
+                && ledger.spend(&UnitArchetype::Ranged.price())
+            {
                 if let Some(card_entity) =
                     spawn_build_order_card(&mut commands, card_parent, &asset_server, 2)
                 {
                     build_queue.queue.push_back(BuildOrder::Ranged(card_entity));
                 }
             }
+            if keyboard_input.just_pressed(KeyCode::KeyT)
+                && ledger.spend(&UnitArchetype::Artillery.price())
+            {
+                if let Some(card_entity) =
+                    spawn_build_order_card(&mut commands, card_parent, &asset_server, 3)
+                {
+                    build_queue
+                        .queue
+                        .push_back(BuildOrder::Artillery(card_entity));
+                }
+            }
+            if keyboard_input.just_pressed(KeyCode::KeyG)
+                && ledger.spend(&UnitArchetype::Repair.price())
+            {
+                if let Some(card_entity) =
+                    spawn_build_order_card(&mut commands, card_parent, &asset_server, 4)
+                {
+                    build_queue.queue.push_back(BuildOrder::Repair(card_entity));
+                }
+            }
         }
     }
 }
@@ -135,8 +373,14 @@ fn build_requested_units(
     time: Res<Time>,
     mother_unit: Query<&Transform, With<MotherUnit>>,
     mut build_queue: ResMut<BuildQueue>,
+    mut supply: ResMut<Supply>,
 ) {
-    if build_queue.queue.len() > 0 {
+    //Production waits until the next ship fits under the supply cap
+    supply.blocked = build_queue
+        .queue
+        .front()
+        .is_some_and(|order| supply.used + order.archetype().supply() > supply.cap);
+    if build_queue.queue.len() > 0 && !supply.blocked {
         build_queue.build_time.tick(time.delta());
         if build_queue.build_time.finished() {
             build_queue.build_time.reset();
@@ -152,21 +396,37 @@ fn build_requested_units(
                     }
                     BuildOrder::Melee(ent) => {
                         cmd.entity(ent).despawn_recursive();
-                        spawn_melee_ally(&mut cmd, spawn_pos, &asset_server)
+                        spawn_melee_ally(&mut cmd, spawn_pos, &asset_server);
                     }
                     BuildOrder::Ranged(ent) => {
                         cmd.entity(ent).despawn_recursive();
                         spawn_ranged_ally(&mut cmd, spawn_pos, &asset_server);
                     }
+                    BuildOrder::Artillery(ent) => {
+                        cmd.entity(ent).despawn_recursive();
+                        spawn_artillery_ally(&mut cmd, spawn_pos, &asset_server);
+                    }
+                    BuildOrder::Repair(ent) => {
+                        cmd.entity(ent).despawn_recursive();
+                        spawn_repair_ally(&mut cmd, spawn_pos, &asset_server);
+                    }
                 }
             }
         }
     }
 }
 
-fn reset_mastermind(mut enemy_brain: ResMut<EnemyBrain>, mut build_queue: ResMut<BuildQueue>) {
+fn reset_mastermind(
+    mut enemy_brain: ResMut<EnemyBrain>,
+    mut build_queue: ResMut<BuildQueue>,
+    mut supply: ResMut<Supply>,
+    difficulty: Res<Difficulty>,
+) {
     *enemy_brain = EnemyBrain::default();
+    enemy_brain.time_between_wave =
+        Timer::from_seconds(difficulty.wave_interval(), TimerMode::Once);
     *build_queue = BuildQueue::default();
+    *supply = Supply::default();
 }
 
 fn enemy_mastermind(
@@ -175,18 +435,23 @@ fn enemy_mastermind(
     time: Res<Time>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    mut map_rng: ResMut<MapRng>,
+    difficulty: Res<Difficulty>,
 ) {
     enemy_brain.time_between_wave.tick(time.delta());
     if enemy_brain.time_between_wave.finished() {
         enemy_brain.time_between_wave.reset();
 
         enemy_brain.current_wave += 1;
-        let number_of_units = (enemy_brain.current_wave as f64).sqrt();
+        let wave_size = ((enemy_brain.current_wave as f32 * difficulty.wave_size_growth()).round()
+            as i32)
+            .max(1);
+        let number_of_units = (wave_size as f64).sqrt();
         let column_count = number_of_units.ceil() as i64;
 
         let mut column_index = 0;
         let mut row_index = 0;
-        let mut rng = rand::thread_rng();
+        let rng = &mut map_rng.waves;
 
         let spawn_side = rng.gen_range(0..4);
         let mut spawn_pos = Vec3::ZERO;
@@ -221,13 +486,28 @@ fn enemy_mastermind(
             }
             _ => {}
         }
-        for i in 0..enemy_brain.current_wave {
+        if enemy_brain.current_wave % enemy_brain.boss_wave_interval == 0 {
+            spawn_boss_enemy(
+                &mut commands,
+                spawn_pos + Vec3::new(0., 160., 0.),
+                &asset_server,
+            );
+        }
+        for i in 0..wave_size {
             column_index += 1;
             if column_index >= column_count {
                 row_index += 1;
                 column_index = 0;
             }
-            if i < 7 {
+            if wave_size >= 4 && i % 4 == 3 {
+                spawn_bomber_enemy(
+                    &mut commands,
+                    spawn_pos
+                        + Vec3::new(80., 0., 0.) * column_index as f32
+                        + Vec3::new(0., -80., 0.) * row_index as f32,
+                    &asset_server,
+                );
+            } else if i < 7 {
                 spawn_melee_enemy(
                     &mut commands,
                     spawn_pos
@@ -248,7 +528,11 @@ fn enemy_mastermind(
     }
 }
 
-fn spawn_melee_enemy(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<AssetServer>) {
+fn spawn_melee_enemy(
+    cmd: &mut Commands,
+    spawn_pos: Vec3,
+    asset_server: &Res<AssetServer>,
+) -> Entity {
     let mut attack_timer = Timer::from_seconds(0.5, TimerMode::Once);
     attack_timer.tick(std::time::Duration::from_secs(1));
     cmd.spawn(SpatialBundle {
@@ -265,17 +549,28 @@ fn spawn_melee_enemy(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<Ass
         current: 70.,
         max_health: 70.,
     })
+    .insert(Armor {
+        kinetic: 0.1,
+        energy: 0.,
+        explosive: 0.,
+    })
     .insert(Velocity(120.))
     .insert(Team(1))
+    .insert(UnitArchetype::PirateMelee)
+    .insert(Veterancy::default())
     .insert(AttackComponent {
         attack_range: 100.,
         attack_amount: 10.,
         time_between_attacks: attack_timer.clone(),
+        damage_type: DamageType::Energy,
+        projectile: ProjectileKind::Ballistic,
+        splash: None,
     })
     .insert(Avoidance {
         last_frame_pos: Vec3::ZERO,
         currently_avoiding: false,
     })
+    .insert(MotionTracker::default())
     .insert(AggressiveLilPig)
     .with_children(|parent| {
         parent
@@ -312,7 +607,8 @@ fn spawn_melee_enemy(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<Ass
                 ..Default::default()
             })
             .insert(RenderLayers::layer(1));
-    });
+    })
+    .id()
 }
 
 fn spawn_ranged_enemy(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<AssetServer>) {
@@ -332,17 +628,28 @@ fn spawn_ranged_enemy(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<As
         current: 70.,
         max_health: 70.,
     })
+    .insert(Armor {
+        kinetic: 0.,
+        energy: 0.1,
+        explosive: 0.,
+    })
     .insert(Velocity(100.))
     .insert(Team(1))
+    .insert(UnitArchetype::PirateRanged)
+    .insert(Veterancy::default())
     .insert(AttackComponent {
         attack_range: 200.,
         attack_amount: 10.,
         time_between_attacks: attack_timer.clone(),
+        damage_type: DamageType::Kinetic,
+        projectile: ProjectileKind::Ballistic,
+        splash: None,
     })
     .insert(Avoidance {
         last_frame_pos: Vec3::ZERO,
         currently_avoiding: false,
     })
+    .insert(MotionTracker::default())
     .insert(AggressiveLilPig)
     .with_children(|parent| {
         parent
@@ -382,76 +689,55 @@ fn spawn_ranged_enemy(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<As
     });
 }
 
-fn spawn_bullet(
-    cmd: &mut Commands,
-    damage: f32,
-    spawn_pos: Vec3,
-    shooter: Entity,
-    target: Entity,
-    target_pos: Vec3,
-    asset_server: &Res<AssetServer>,
-) {
-    let mut start_transform = Transform::from_translation(spawn_pos);
-    start_transform.scale = Vec3::new(0.1, 0.3, 1.);
-
-    let diff = target_pos - spawn_pos;
-    let angle = diff.y.atan2(diff.x) - FRAC_PI_2;
-    start_transform.rotation = Quat::from_axis_angle(Vec3::Z, angle);
-
-    cmd.spawn(SpriteBundle {
-        texture: asset_server.load("effect_yellow.png"),
-        transform: start_transform,
-        ..Default::default()
-    })
-    .insert(FlyingBullet {
-        target: target,
-        damage: damage,
-        speed: 1000.,
-        shooter: shooter,
-    })
-    .insert(FaceMovementDirection {
-        face_to_pos: target_pos,
-    });
-}
-
-fn spawn_units(mut cmd: Commands, asset_server: Res<AssetServer>) {
-    let mut attack_timer = Timer::from_seconds(0.5, TimerMode::Once);
+fn spawn_bomber_enemy(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<AssetServer>) {
+    let mut attack_timer = Timer::from_seconds(2.0, TimerMode::Once);
     attack_timer.tick(std::time::Duration::from_secs(1));
-
-    //SPAWNMOTHERSHIP
     cmd.spawn(SpatialBundle {
-        transform: Transform::from_translation(Vec3::new(0., -100., 0.)),
+        transform: Transform::from_translation(spawn_pos),
         ..Default::default()
     })
-    .insert(Collider::cuboid(50.0, 50.0))
+    .insert(Collider::cuboid(25.0, 25.0))
     .insert(Sensor)
     .insert(Selectable)
-    .insert(Velocity(50.))
     .insert(UnitCommandList {
         commands: Vec::new(),
     })
     .insert(Health {
-        current: 300.,
-        max_health: 300.,
+        current: 70.,
+        max_health: 70.,
     })
-    .insert(Team(0))
-    .insert(Avoidance {
-        last_frame_pos: Vec3::ZERO,
-        currently_avoiding: false,
+    .insert(Armor {
+        kinetic: 0.,
+        energy: 0.1,
+        explosive: 0.,
     })
-    .insert(MotherUnit)
+    .insert(Velocity(80.))
+    .insert(Team(1))
+    .insert(UnitArchetype::PirateBomber)
+    .insert(Veterancy::default())
     .insert(AttackComponent {
-        attack_range: 300.,
-        attack_amount: 1.,
+        attack_range: 150.,
+        attack_amount: 25.,
         time_between_attacks: attack_timer.clone(),
+        damage_type: DamageType::Explosive,
+        projectile: ProjectileKind::Ballistic,
+        splash: Some(Splash {
+            radius: 120.,
+            friendly_fire: false,
+        }),
+    })
+    .insert(Avoidance {
+        last_frame_pos: Vec3::ZERO,
+        currently_avoiding: false,
     })
+    .insert(MotionTracker::default())
+    .insert(AggressiveLilPig)
     .with_children(|parent| {
         parent
             .spawn(SpriteBundle {
-                texture: asset_server.load("units/station_B.png"),
+                texture: asset_server.load("units/enemy_A.png"),
                 sprite: Sprite {
-                    color: Color::srgba(1., 1., 1., 1.),
-                    custom_size: Some(Vec2::new(128., 128.)),
+                    color: Color::srgb(1., 0.7, 0.2),
                     ..default()
                 },
                 ..Default::default()
@@ -462,9 +748,9 @@ fn spawn_units(mut cmd: Commands, asset_server: Res<AssetServer>) {
         parent
             .spawn(SpriteBundle {
                 texture: asset_server.load("healthbar.png"),
-                transform: Transform::from_translation(Vec3::new(0., -60., 0.)),
+                transform: Transform::from_translation(Vec3::new(0., -30., 0.)),
                 sprite: Sprite {
-                    color: Color::srgba(0., 1., 0., 1.),
+                    color: Color::srgba(1., 0., 0., 1.),
                     ..default()
                 },
                 ..Default::default()
@@ -474,171 +760,718 @@ fn spawn_units(mut cmd: Commands, asset_server: Res<AssetServer>) {
             .spawn(SpriteBundle {
                 texture: asset_server.load("units/meteor_small.png"),
                 sprite: Sprite {
-                    color: Color::srgba(0., 1., 0., 1.),
-                    custom_size: Some(Vec2::new(140., 140.)),
+                    color: Color::srgba(1., 0., 0., 1.),
+                    custom_size: Some(Vec2::new(100., 100.)),
                     ..default()
                 },
                 ..Default::default()
             })
             .insert(RenderLayers::layer(1));
     });
-
-    //SPAWN MINERS
-    for i in -2..2 {
-        spawn_miner_ally(
-            &mut cmd,
-            Vec3::new((i as f32 % 10.) * 100., 45., 0.),
-            &asset_server,
-        );
-    }
-
-    //SPAWN RANGERS
-    for i in -1..1 {
-        spawn_ranged_ally(
-            &mut cmd,
-            Vec3::new((i as f32 % 10.) * 100., 45. * i as f32 / 10., 0.),
-            &asset_server,
-        );
-    }
 }
 
-#[derive(Component)]
-pub struct AggressiveLilPig;
+const BOSS_BOUNTY: f32 = 300.;
 
 #[derive(Component)]
-pub struct MildAggression;
-
-//ATTACK ENEMIES WHEN IN 400.0 range
-fn handle_mildly_aggressive_pigs(
-    mut aggressive_q: Query<(&mut UnitCommandList, Entity), With<MildAggression>>,
-    all_units: Query<(&Transform, &Team, Entity)>,
-) {
-    for (mut command_list, e) in aggressive_q.iter_mut() {
-        if command_list.commands.len() == 0 {
-            let mut aggressive_pig_pos = None;
-            let mut pig_team_nr = None;
-            if let Ok((pig_tr, pig_team, _e)) = all_units.get(e) {
-                aggressive_pig_pos = Some(pig_tr.translation);
-                pig_team_nr = Some(pig_team.0);
-            }
-            if aggressive_pig_pos != None {
-                let mut closest_enemy_unit: (Option<Entity>, f32) = (None, f32::MAX);
-                for (unit_tr, unit_team, unit_entity) in all_units.iter() {
-                    if unit_team.0 != pig_team_nr.unwrap() {
-                        let diff_vec = unit_tr.translation - aggressive_pig_pos.unwrap();
-                        if diff_vec.length() < closest_enemy_unit.1 {
-                            closest_enemy_unit.1 = diff_vec.length();
-                            closest_enemy_unit.0 = Some(unit_entity);
-                        }
-                    }
-                }
-
-                if let Some(enemy_entity) = closest_enemy_unit.0 {
-                    if closest_enemy_unit.1 < 400.0 {
-                        command_list
-                            .commands
-                            .push(UnitCommand::AttackEntity(enemy_entity));
-                    }
-                }
-            }
-        }
-    }
-}
-//ATTACK CLOSEST ENEMY NO MATTER HOW FAR AWAY
-fn handle_aggressive_pigs(
-    mut aggressive_q: Query<(&mut UnitCommandList, Entity), With<AggressiveLilPig>>,
-    all_units: Query<(&Transform, &Team, Entity)>,
-) {
-    for (mut command_list, e) in aggressive_q.iter_mut() {
-        if command_list.commands.len() == 0 {
-            let mut aggressive_pig_pos = None;
-            let mut pig_team_nr = None;
-            if let Ok((pig_tr, pig_team, _e)) = all_units.get(e) {
-                aggressive_pig_pos = Some(pig_tr.translation);
-                pig_team_nr = Some(pig_team.0);
-            }
-            if aggressive_pig_pos != None {
-                let mut closest_enemy_unit: (Option<Entity>, f32) = (None, f32::MAX);
-                for (unit_tr, unit_team, unit_entity) in all_units.iter() {
-                    if unit_team.0 != pig_team_nr.unwrap() {
-                        let diff_vec = unit_tr.translation - aggressive_pig_pos.unwrap();
-                        if diff_vec.length() < closest_enemy_unit.1 {
-                            closest_enemy_unit.1 = diff_vec.length();
-                            closest_enemy_unit.0 = Some(unit_entity);
-                        }
-                    }
-                }
+pub struct Boss;
 
-                if let Some(enemy_entity) = closest_enemy_unit.0 {
-                    command_list
-                        .commands
-                        .push(UnitCommand::AttackEntity(enemy_entity));
-                }
-            }
-        }
-    }
-}
+//Extra guns that pick their own targets independently of the ship's orders
+#[derive(Component)]
+pub struct Hardpoints(pub Vec<Hardpoint>);
 
-#[derive(Component, Clone, Copy)]
-pub enum UnitCommand {
-    MoveToPos(Vec3),
-    AttackEntity(Entity),
-    MineEntity(Entity),
-    ReturnCargoToUnit(Entity, Option<Entity>),
-    Completed,
+pub struct Hardpoint {
+    pub offset: Vec3,
+    pub weapon: AttackComponent,
 }
 
+//Disables miners caught in the blast for a while
 #[derive(Component)]
-pub struct MiningComponent {
-    pub current_carry: f32,
-    pub max_carry: f32,
-    pub time_between_mine: Timer,
+pub struct EmpAbility {
+    pub cooldown: Timer,
+    pub radius: f32,
+    pub stun_seconds: f32,
 }
 
 #[derive(Component)]
-pub struct UnitCommandList {
-    commands: Vec<UnitCommand>,
+pub struct DroneBay {
+    pub cooldown: Timer,
+    pub drones_per_launch: u32,
 }
 
+//Launched by a boss, worth no salvage and no kill XP so they can't be farmed
 #[derive(Component)]
-pub struct Velocity(pub f32);
+pub struct Drone;
 
 #[derive(Component)]
-pub struct AttackComponent {
-    attack_amount: f32,
-    attack_range: f32,
-    time_between_attacks: Timer,
+pub struct Stunned {
+    pub timer: Timer,
 }
 
-fn command_units(
-    buttons: Res<ButtonInput<MouseButton>>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    currently_selected: Res<CurrentlySelected>,
-    q_window: Query<&Window, With<PrimaryWindow>>,
-    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
-    rapier_context: Res<RapierContext>,
-    mut q_unit_command_list: Query<&mut UnitCommandList>,
-    q_team: Query<&Team>,
-    q_mining: Query<&MiningComponent>,
-    q_mineable: Query<&Mineable>,
-) {
-    if buttons.just_pressed(MouseButton::Right) {
-        let (camera, camera_transform) = q_camera.single();
-        let window = q_window.single();
-        let mut click_pos = Vec2::new(0., 0.);
-        if let Some(world_position) = window
-            .cursor_position()
-            .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
-            .map(|ray| ray.origin.truncate())
-        {
-            click_pos = world_position;
-        }
+fn spawn_boss_enemy(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<AssetServer>) {
+    let mut attack_timer = Timer::from_seconds(1.0, TimerMode::Once);
+    attack_timer.tick(std::time::Duration::from_secs(1));
+    let main_gun = AttackComponent {
+        attack_range: 250.,
+        attack_amount: 20.,
+        time_between_attacks: attack_timer.clone(),
+        damage_type: DamageType::Kinetic,
+        projectile: ProjectileKind::Ballistic,
+        splash: None,
+    };
+    let mut laser = main_gun.clone();
+    laser.attack_amount = 6.;
+    laser.damage_type = DamageType::Energy;
+    laser.time_between_attacks = Timer::from_seconds(0.4, TimerMode::Once);
+    let mut missile = main_gun.clone();
+    missile.attack_range = 400.;
+    missile.attack_amount = 15.;
+    missile.damage_type = DamageType::Explosive;
+    missile.projectile = ProjectileKind::Homing;
+    missile.time_between_attacks = Timer::from_seconds(2.5, TimerMode::Once);
 
-        let filter = QueryFilter::default();
-        let mut clicked_units = Vec::new();
-        rapier_context.intersections_with_point(click_pos, filter, |entity| {
-            clicked_units.push(entity);
-            true
+    cmd.spawn(SpatialBundle {
+        transform: Transform::from_translation(spawn_pos),
+        ..Default::default()
+    })
+    .insert(Collider::cuboid(70.0, 70.0))
+    .insert(Sensor)
+    .insert(Selectable)
+    .insert(UnitCommandList {
+        commands: Vec::new(),
+    })
+    .insert(Health {
+        current: 1500.,
+        max_health: 1500.,
+    })
+    .insert(Armor {
+        kinetic: 0.4,
+        energy: 0.2,
+        explosive: 0.1,
+    })
+    .insert(Shield {
+        current: 300.,
+        capacity: 300.,
+        regen_rate: 20.,
+        regen_delay: Timer::from_seconds(5.0, TimerMode::Once),
+    })
+    .insert(Velocity(40.))
+    .insert(Team(1))
+    .insert(UnitArchetype::PirateBoss)
+    .insert(Veterancy::default())
+    .insert(main_gun)
+    .insert(Hardpoints(vec![
+        Hardpoint {
+            offset: Vec3::new(-50., 20., 0.),
+            weapon: laser.clone(),
+        },
+        Hardpoint {
+            offset: Vec3::new(50., 20., 0.),
+            weapon: laser,
+        },
+        Hardpoint {
+            offset: Vec3::new(0., -40., 0.),
+            weapon: missile,
+        },
+    ]))
+    .insert(EmpAbility {
+        cooldown: Timer::from_seconds(12.0, TimerMode::Once),
+        radius: 350.,
+        stun_seconds: 4.,
+    })
+    .insert(DroneBay {
+        cooldown: Timer::from_seconds(15.0, TimerMode::Once),
+        drones_per_launch: 2,
+    })
+    .insert(Boss)
+    .insert(Avoidance {
+        last_frame_pos: Vec3::ZERO,
+        currently_avoiding: false,
+    })
+    .insert(MotionTracker::default())
+    .insert(AggressiveLilPig)
+    .with_children(|parent| {
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("units/enemy_A.png"),
+                sprite: Sprite {
+                    color: Color::srgb(0.8, 0.2, 0.2),
+                    custom_size: Some(Vec2::new(160., 160.)),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(FaceMovementDirection {
+                face_to_pos: Vec3::ZERO,
+            });
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("healthbar.png"),
+                transform: Transform::from_translation(Vec3::new(0., -90., 0.))
+                    .with_scale(Vec3::new(1., 1.5, 1.)),
+                sprite: Sprite {
+                    color: Color::srgba(1., 0., 0., 1.),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(HealthBar);
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("units/meteor_small.png"),
+                sprite: Sprite {
+                    color: Color::srgba(1., 0., 0., 1.),
+                    custom_size: Some(Vec2::new(220., 220.)),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(RenderLayers::layer(1));
+    });
+}
+
+fn fire_hardpoints(
+    mut cmd: Commands,
+    time: Res<Time>,
+    mut ships: Query<(Entity, &Transform, &Team, &mut Hardpoints), Without<Stunned>>,
+    targets: Query<(Entity, &Transform, &Team)>,
+    motion_q: Query<&MotionTracker>,
+    diplomacy: Res<Diplomacy>,
+    asset_server: Res<AssetServer>,
+) {
+    for (ship_e, ship_tr, ship_team, mut hardpoints) in ships.iter_mut() {
+        for hardpoint in hardpoints.0.iter_mut() {
+            hardpoint.weapon.time_between_attacks.tick(time.delta());
+            if !hardpoint.weapon.time_between_attacks.finished() {
+                continue;
+            }
+            let gun_pos = ship_tr.translation + ship_tr.rotation * hardpoint.offset;
+            let mut closest: (Option<(Entity, Vec3)>, f32) = (None, hardpoint.weapon.attack_range);
+            for (target_e, target_tr, target_team) in targets.iter() {
+                let dist = (target_tr.translation - gun_pos).length();
+                if diplomacy.is_hostile(ship_team.0, target_team.0) && dist < closest.1 {
+                    closest = (Some((target_e, target_tr.translation)), dist);
+                }
+            }
+            if let Some((target_e, target_pos)) = closest.0 {
+                hardpoint.weapon.time_between_attacks.reset();
+                let mut aim_pos = target_pos;
+                if let Ok(target_motion) = motion_q.get(target_e) {
+                    aim_pos =
+                        lead_target(gun_pos, target_pos, target_motion.velocity, BULLET_SPEED);
+                }
+                spawn_bullet(
+                    &mut cmd,
+                    &hardpoint.weapon,
+                    gun_pos - Vec3::new(0., 0., 1.),
+                    (ship_e, Some(ship_team.0)),
+                    target_e,
+                    aim_pos,
+                    &asset_server,
+                );
+            }
+        }
+    }
+}
+
+fn use_emp_abilities(
+    mut cmd: Commands,
+    time: Res<Time>,
+    mut casters: Query<(&Transform, &Team, &mut EmpAbility)>,
+    miners: Query<(Entity, &Transform, &Team), With<MiningComponent>>,
+    diplomacy: Res<Diplomacy>,
+    asset_server: Res<AssetServer>,
+) {
+    for (caster_tr, caster_team, mut emp) in casters.iter_mut() {
+        emp.cooldown.tick(time.delta());
+        if !emp.cooldown.finished() {
+            continue;
+        }
+        let mut victims = Vec::new();
+        for (miner_e, miner_tr, miner_team) in miners.iter() {
+            if diplomacy.is_hostile(caster_team.0, miner_team.0)
+                && (miner_tr.translation - caster_tr.translation).length() < emp.radius
+            {
+                victims.push(miner_e);
+            }
+        }
+        if victims.is_empty() {
+            continue;
+        }
+        emp.cooldown.reset();
+        spawn_explosion_visual(&mut cmd, &asset_server, caster_tr.translation, emp.radius);
+        for victim in victims {
+            cmd.entity(victim).try_insert(Stunned {
+                timer: Timer::from_seconds(emp.stun_seconds, TimerMode::Once),
+            });
+        }
+    }
+}
+
+fn launch_drones(
+    mut cmd: Commands,
+    time: Res<Time>,
+    mut carriers: Query<(&Transform, &mut DroneBay)>,
+    asset_server: Res<AssetServer>,
+) {
+    for (carrier_tr, mut drone_bay) in carriers.iter_mut() {
+        drone_bay.cooldown.tick(time.delta());
+        if drone_bay.cooldown.finished() {
+            drone_bay.cooldown.reset();
+            for i in 0..drone_bay.drones_per_launch {
+                let offset = Vec3::new(-60. + 120. * i as f32, -90., 0.);
+                let drone =
+                    spawn_melee_enemy(&mut cmd, carrier_tr.translation + offset, &asset_server);
+                cmd.entity(drone).insert(Drone);
+            }
+        }
+    }
+}
+
+fn tick_stuns(mut cmd: Commands, time: Res<Time>, mut stunned_q: Query<(Entity, &mut Stunned)>) {
+    for (e, mut stunned) in stunned_q.iter_mut() {
+        stunned.timer.tick(time.delta());
+        if stunned.timer.finished() {
+            cmd.entity(e).remove::<Stunned>();
+        }
+    }
+}
+
+fn pay_boss_bounty(
+    mut ev_destroyed: EventReader<UnitDestroyed>,
+    mut ledger: ResMut<ResourceLedger>,
+) {
+    for destroyed in ev_destroyed.read() {
+        if destroyed.archetype == Some(UnitArchetype::PirateBoss) {
+            ledger.add(ResourceKind::Ore, BOSS_BOUNTY);
+        }
+    }
+}
+
+pub fn spawn_bullet(
+    cmd: &mut Commands,
+    attack_comp: &AttackComponent,
+    spawn_pos: Vec3,
+    (shooter, shooter_team): (Entity, Option<i32>),
+    target: Entity,
+    aim_pos: Vec3,
+    asset_server: &Res<AssetServer>,
+) {
+    let mut start_transform = Transform::from_translation(spawn_pos);
+    start_transform.scale = Vec3::new(0.1, 0.3, 1.);
+
+    let diff = (aim_pos - spawn_pos).truncate().extend(0.);
+    let angle = diff.y.atan2(diff.x) - FRAC_PI_2;
+    start_transform.rotation = Quat::from_axis_angle(Vec3::Z, angle);
+
+    //Splash shells detonate at the aimed position if they don't hit anything on the way
+    let mut range = attack_comp.attack_range * 1.5;
+    if attack_comp.splash.is_some() {
+        range = diff.length();
+    }
+
+    let mut bullet = cmd.spawn(SpriteBundle {
+        texture: asset_server.load("effect_yellow.png"),
+        transform: start_transform,
+        ..Default::default()
+    });
+    bullet.insert(FlyingBullet {
+        target,
+        damage: attack_comp.attack_amount,
+        speed: BULLET_SPEED,
+        shooter,
+        shooter_team,
+        direction: diff.normalize_or_zero(),
+        range_left: range,
+        kind: attack_comp.projectile,
+        damage_type: attack_comp.damage_type,
+        splash: attack_comp.splash,
+    });
+    if attack_comp.projectile == ProjectileKind::Homing {
+        bullet.insert(FaceMovementDirection {
+            face_to_pos: aim_pos,
+        });
+    }
+}
+
+//Where to shoot so a bullet meets a target that keeps its current velocity
+pub fn lead_target(
+    shooter_pos: Vec3,
+    target_pos: Vec3,
+    target_vel: Vec3,
+    bullet_speed: f32,
+) -> Vec3 {
+    let to_target = (target_pos - shooter_pos).truncate();
+    let vel = target_vel.truncate();
+    let a = vel.length_squared() - bullet_speed * bullet_speed;
+    let b = 2.0 * to_target.dot(vel);
+    let c = to_target.length_squared();
+
+    let mut time_to_hit = None;
+    if a.abs() < 0.001 {
+        if b.abs() > 0.001 {
+            time_to_hit = Some(-c / b);
+        }
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant >= 0.0 {
+            let root = discriminant.sqrt();
+            let t1 = (-b - root) / (2.0 * a);
+            let t2 = (-b + root) / (2.0 * a);
+            let t = if t1 > 0.0 && t2 > 0.0 {
+                t1.min(t2)
+            } else {
+                t1.max(t2)
+            };
+            time_to_hit = Some(t);
+        }
+    }
+
+    match time_to_hit {
+        Some(t) if t > 0.0 => target_pos + target_vel * t,
+        _ => target_pos,
+    }
+}
+
+fn spawn_units(mut cmd: Commands, asset_server: Res<AssetServer>) {
+    let mut attack_timer = Timer::from_seconds(0.5, TimerMode::Once);
+    attack_timer.tick(std::time::Duration::from_secs(1));
+
+    //SPAWNMOTHERSHIP
+    cmd.spawn(SpatialBundle {
+        transform: Transform::from_translation(Vec3::new(0., -100., 0.)),
+        ..Default::default()
+    })
+    .insert(Collider::cuboid(50.0, 50.0))
+    .insert(Sensor)
+    .insert(Selectable)
+    .insert(Velocity(50.))
+    .insert(UnitCommandList {
+        commands: Vec::new(),
+    })
+    .insert(CargoDepot)
+    .insert(SupplyProvider(20))
+    .insert(Health {
+        current: 300.,
+        max_health: 300.,
+    })
+    .insert(Armor {
+        kinetic: 0.6,
+        energy: 0.2,
+        explosive: 0.,
+    })
+    .insert(Shield {
+        current: 150.,
+        capacity: 150.,
+        regen_rate: 15.,
+        regen_delay: Timer::from_seconds(4.0, TimerMode::Once),
+    })
+    .insert(Team(0))
+    .insert(UnitArchetype::Mothership)
+    .insert(Veterancy::default())
+    .insert(Avoidance {
+        last_frame_pos: Vec3::ZERO,
+        currently_avoiding: false,
+    })
+    .insert(MotionTracker::default())
+    .insert(MotherUnit)
+    .insert(Abilities(vec![
+        Ability::new(
+            "Warp",
+            AbilityKind::WarpJump { max_distance: 400. },
+            20.,
+            ResourceCost::new(0., 25., 0.),
+            KeyCode::KeyZ,
+        ),
+        Ability::new(
+            "Shield",
+            AbilityKind::ShieldBurst {
+                radius: 250.,
+                amount: 40.,
+                duration: 8.,
+            },
+            30.,
+            ResourceCost::new(20., 0., 20.),
+            KeyCode::KeyX,
+        ),
+        Ability::new(
+            "Tractor",
+            AbilityKind::TractorBeam {
+                range: 700.,
+                pull_speed: 80.,
+                duration: 6.,
+            },
+            25.,
+            ResourceCost::new(10., 0., 0.),
+            KeyCode::KeyC,
+        ),
+        Ability::new(
+            "Nudge",
+            AbilityKind::Nudge {
+                radius: 400.,
+                speed: 80.,
+            },
+            15.,
+            ResourceCost::new(0., 15., 0.),
+            KeyCode::KeyH,
+        ),
+    ]))
+    .insert(RepairComponent {
+        heal_per_second: 5.,
+        range: 200.,
+        ore_per_hp: 0.2,
+        auto_cast: true,
+        stationary: true,
+    })
+    .insert(AttackComponent {
+        attack_range: 300.,
+        attack_amount: 1.,
+        time_between_attacks: attack_timer.clone(),
+        damage_type: DamageType::Explosive,
+        projectile: ProjectileKind::Homing,
+        splash: None,
+    })
+    .with_children(|parent| {
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("units/station_B.png"),
+                sprite: Sprite {
+                    color: Color::srgba(1., 1., 1., 1.),
+                    custom_size: Some(Vec2::new(128., 128.)),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(FaceMovementDirection {
+                face_to_pos: Vec3::ZERO,
+            });
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("healthbar.png"),
+                transform: Transform::from_translation(Vec3::new(0., -60., 0.)),
+                sprite: Sprite {
+                    color: Color::srgba(0., 1., 0., 1.),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(HealthBar);
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("healthbar.png"),
+                transform: Transform::from_translation(Vec3::new(0., -70., 0.)),
+                sprite: Sprite {
+                    color: Color::srgba(0., 0.6, 1., 1.),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(ShieldBar);
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("units/meteor_small.png"),
+                sprite: Sprite {
+                    color: Color::srgba(0., 1., 0., 1.),
+                    custom_size: Some(Vec2::new(140., 140.)),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(RenderLayers::layer(1));
+    });
+
+    //SPAWN MINERS
+    for i in -2..2 {
+        spawn_miner_ally(
+            &mut cmd,
+            Vec3::new((i as f32 % 10.) * 100., 45., 0.),
+            &asset_server,
+        );
+    }
+
+    //SPAWN RANGERS
+    for i in -1..1 {
+        spawn_ranged_ally(
+            &mut cmd,
+            Vec3::new((i as f32 % 10.) * 100., 45. * i as f32 / 10., 0.),
+            &asset_server,
+        );
+    }
+}
+
+#[derive(Component)]
+pub struct AggressiveLilPig;
+
+#[derive(Component)]
+pub struct MildAggression;
+
+//ATTACK ENEMIES WHEN IN 400.0 range
+fn handle_mildly_aggressive_pigs(
+    mut aggressive_q: Query<(&mut UnitCommandList, Entity), With<MildAggression>>,
+    all_units: Query<(&Transform, &Team, Entity)>,
+    diplomacy: Res<Diplomacy>,
+) {
+    for (mut command_list, e) in aggressive_q.iter_mut() {
+        if command_list.commands.len() == 0 {
+            if let Ok((pig_tr, pig_team, _e)) = all_units.get(e) {
+                if let Some((enemy_entity, distance)) =
+                    closest_hostile(pig_tr.translation, pig_team.0, &all_units, &diplomacy)
+                {
+                    if distance < 400.0 {
+                        command_list
+                            .commands
+                            .push(UnitCommand::AttackEntity(enemy_entity));
+                    }
+                }
+            }
+        }
+    }
+}
+//ATTACK CLOSEST ENEMY NO MATTER HOW FAR AWAY
+fn handle_aggressive_pigs(
+    mut aggressive_q: Query<(&mut UnitCommandList, Entity), With<AggressiveLilPig>>,
+    all_units: Query<(&Transform, &Team, Entity)>,
+    diplomacy: Res<Diplomacy>,
+) {
+    for (mut command_list, e) in aggressive_q.iter_mut() {
+        if command_list.commands.len() == 0 {
+            if let Ok((pig_tr, pig_team, _e)) = all_units.get(e) {
+                if let Some((enemy_entity, _distance)) =
+                    closest_hostile(pig_tr.translation, pig_team.0, &all_units, &diplomacy)
+                {
+                    command_list
+                        .commands
+                        .push(UnitCommand::AttackEntity(enemy_entity));
+                }
+            }
+        }
+    }
+}
+
+//Closest unit of a team that is hostile to the given team, with its distance
+pub fn closest_hostile(
+    pos: Vec3,
+    team: i32,
+    all_units: &Query<(&Transform, &Team, Entity)>,
+    diplomacy: &Diplomacy,
+) -> Option<(Entity, f32)> {
+    let mut closest_enemy_unit: (Option<Entity>, f32) = (None, f32::MAX);
+    for (unit_tr, unit_team, unit_entity) in all_units.iter() {
+        if diplomacy.is_hostile(team, unit_team.0) {
+            let diff_vec = unit_tr.translation - pos;
+            if diff_vec.length() < closest_enemy_unit.1 {
+                closest_enemy_unit.1 = diff_vec.length();
+                closest_enemy_unit.0 = Some(unit_entity);
+            }
+        }
+    }
+    closest_enemy_unit.0.map(|e| (e, closest_enemy_unit.1))
+}
+
+#[derive(Component, Clone, Copy)]
+pub enum UnitCommand {
+    MoveToPos(Vec3),
+    AttackEntity(Entity),
+    MineEntity(Entity),
+    ReturnCargoToUnit(Entity, Option<Entity>),
+    RepairEntity(Entity),
+    BuildStructure(Entity),
+    Completed,
+}
+
+//Miners can unload their cargo here
+#[derive(Component)]
+pub struct CargoDepot;
+
+#[derive(Component)]
+pub struct MiningComponent {
+    pub current_carry: f32,
+    pub carried_kind: Option<ResourceKind>,
+    //Where the miner last worked, idle miners look for new asteroids around it
+    pub work_area: Option<Vec3>,
+    pub auto_mine: bool,
+    pub max_carry: f32,
+    pub time_between_mine: Timer,
+}
+
+//Heals friendly units over time, paid for with ore
+#[derive(Component)]
+pub struct RepairComponent {
+    pub heal_per_second: f32,
+    pub range: f32,
+    pub ore_per_hp: f32,
+    pub auto_cast: bool,
+    //Stationary repairers heal everything in range instead of flying to a target
+    pub stationary: bool,
+}
+
+#[derive(Component)]
+pub struct UnitCommandList {
+    pub commands: Vec<UnitCommand>,
+}
+
+#[derive(Component)]
+pub struct Velocity(pub f32);
+
+#[derive(Component, Clone)]
+pub struct AttackComponent {
+    pub attack_amount: f32,
+    pub attack_range: f32,
+    pub time_between_attacks: Timer,
+    pub projectile: ProjectileKind,
+    pub damage_type: DamageType,
+    pub splash: Option<Splash>,
+}
+
+#[derive(Clone, Copy)]
+pub struct Splash {
+    pub radius: f32,
+    pub friendly_fire: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ProjectileKind {
+    //Flies straight along the heading it was launched with
+    Ballistic,
+    //Follows its target, used for missiles
+    Homing,
+}
+
+pub const BULLET_SPEED: f32 = 1000.;
+
+fn command_units(
+    buttons: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    currently_selected: Res<CurrentlySelected>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    rapier_context: Res<RapierContext>,
+    mut q_unit_command_list: Query<&mut UnitCommandList>,
+    q_team: Query<&Team>,
+    mut q_mining: Query<&mut MiningComponent>,
+    q_mineable: Query<&Mineable>,
+    q_repair: Query<&RepairComponent>,
+    q_depot: Query<(), (With<CargoDepot>, Without<UnderConstruction>)>,
+    diplomacy: Res<Diplomacy>,
+    local_player: Res<LocalPlayer>,
+    placement: Res<PlacementMode>,
+) {
+    if buttons.just_pressed(MouseButton::Right) && !placement.blocks_input() {
+        let (camera, camera_transform) = q_camera.single();
+        let window = q_window.single();
+        let mut click_pos = Vec2::new(0., 0.);
+        if let Some(world_position) = window
+            .cursor_position()
+            .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+            .map(|ray| ray.origin.truncate())
+        {
+            click_pos = world_position;
+        }
+
+        let filter = QueryFilter::default();
+        let mut clicked_units = Vec::new();
+        rapier_context.intersections_with_point(click_pos, filter, |entity| {
+            clicked_units.push(entity);
+            true
         });
 
         let number_of_units = (currently_selected.ent.len() as f64).sqrt();
@@ -654,16 +1487,41 @@ fn command_units(
                 if let Ok(_) = q_mining.get(*e) {
                     has_mining_comp = true;
                 }
+                let can_repair = q_repair.get(*e).is_ok();
+                //Miners sent to a depot go back to the asteroid they were working on
+                let mut last_mineable = None;
+                for c in unit_command_list.commands.iter() {
+                    if let UnitCommand::MineEntity(mineable_e) = c {
+                        last_mineable = Some(*mineable_e);
+                        break;
+                    }
+                }
                 for clicked_e in clicked_units.iter() {
                     if !keyboard_input.pressed(KeyCode::ShiftLeft) {
                         unit_command_list.commands = Vec::new();
                     }
                     if e != clicked_e {
                         if let Ok(clicked_team) = q_team.get(*clicked_e) {
-                            if clicked_team.0 != 0 {
+                            if diplomacy.is_hostile(local_player.0, clicked_team.0) {
                                 unit_command_list
                                     .commands
                                     .push(UnitCommand::AttackEntity(*clicked_e));
+                            } else if has_mining_comp
+                                && q_depot.get(*clicked_e).is_ok()
+                                && clicked_team.0 == local_player.0
+                            {
+                                unit_command_list
+                                    .commands
+                                    .push(UnitCommand::ReturnCargoToUnit(
+                                        *clicked_e,
+                                        last_mineable,
+                                    ));
+                            } else if can_repair
+                                && diplomacy.is_ally(local_player.0, clicked_team.0)
+                            {
+                                unit_command_list
+                                    .commands
+                                    .push(UnitCommand::RepairEntity(*clicked_e));
                             }
                         } else if let Ok(_mineable) = q_mineable.get(*clicked_e) {
                             if has_mining_comp {
@@ -672,7 +1530,6 @@ fn command_units(
                                     .push(UnitCommand::MineEntity(*clicked_e));
                             }
                         }
-                        //if not: check if material depot: Add unitcommand::return
                         moving_to_unit = true;
                     }
                 }
@@ -681,6 +1538,10 @@ fn command_units(
                     if !keyboard_input.pressed(KeyCode::ShiftLeft) {
                         unit_command_list.commands = Vec::new();
                     }
+                    //Miners sent somewhere else stop returning to their old asteroid field
+                    if let Ok(mut mining_comp) = q_mining.get_mut(*e) {
+                        mining_comp.work_area = None;
+                    }
                     unit_command_list.commands.push(UnitCommand::MoveToPos(
                         click_pos.extend(0.)
                             + Vec3::new(80., 0., 0.) * column_index as f32
@@ -699,21 +1560,28 @@ fn command_units(
 
 fn move_units(
     time: Res<Time>,
-    mut units: Query<(
-        Entity,
-        &Velocity,
-        &mut UnitCommandList,
-        &mut AttackComponent,
-        &Children,
-    )>,
+    mut units: Query<
+        (
+            Entity,
+            &Velocity,
+            &mut UnitCommandList,
+            &mut AttackComponent,
+            &Children,
+        ),
+        Without<Stunned>,
+    >,
     mut mining_component_q: Query<&mut MiningComponent>,
     mut transforms: Query<(&mut Transform, &GlobalTransform)>,
     mut face_direction_q: Query<&mut FaceMovementDirection>,
     mut commands: Commands,
-    mut mineables_q: Query<&mut Mineable>,
-    mut mineral_resources: ResMut<MineralResources>,
-    mother_unit: Query<Entity, With<MotherUnit>>,
+    mut mineables_q: Query<(&mut Mineable, Option<&mut MiningSlots>)>,
+    mut ledger: ResMut<ResourceLedger>,
+    depots: DepotQuery,
     asset_server: Res<AssetServer>,
+    motion_q: Query<&MotionTracker>,
+    team_q: Query<&Team>,
+    repair_q: Query<&RepairComponent>,
+    construction_q: Query<(), With<UnderConstruction>>,
 ) {
     for (e, vel, mut command_list, mut attack_comp, children) in units.iter_mut() {
         if command_list.commands.len() > 0 {
@@ -757,13 +1625,22 @@ fn move_units(
                             if attack_comp.time_between_attacks.finished() {
                                 attack_comp.time_between_attacks.reset();
 
+                                let mut aim_pos = enemy_tr.translation;
+                                if let Ok(enemy_motion) = motion_q.get(*enemy) {
+                                    aim_pos = lead_target(
+                                        tr.translation,
+                                        enemy_tr.translation,
+                                        enemy_motion.velocity,
+                                        BULLET_SPEED,
+                                    );
+                                }
                                 spawn_bullet(
                                     &mut commands,
-                                    attack_comp.attack_amount,
+                                    &attack_comp,
                                     tr.translation - Vec3::new(0., 0., 1.),
-                                    e,
+                                    (e, team_q.get(e).ok().map(|t| t.0)),
                                     *enemy,
-                                    enemy_tr.translation,
+                                    aim_pos,
                                     &asset_server,
                                 );
                             }
@@ -775,8 +1652,14 @@ fn move_units(
                 UnitCommand::MineEntity(mineable_entity) => {
                     if let Ok(mut mining_comp) = mining_component_q.get_mut(e) {
                         mining_comp.time_between_mine.tick(time.delta());
-                        if let Ok(mut mineable) = mineables_q.get_mut(*mineable_entity) {
-                            if mining_comp.current_carry < mining_comp.max_carry {
+                        if let Ok((mut mineable, mut slots)) = mineables_q.get_mut(*mineable_entity)
+                        {
+                            //Cargo holds one kind at a time, unload before switching
+                            let other_kind_in_hold = mining_comp.current_carry > 0.
+                                && mining_comp.carried_kind != Some(mineable.kind);
+                            if mining_comp.current_carry < mining_comp.max_carry
+                                && !other_kind_in_hold
+                            {
                                 if let Ok(
                                     [(mut tr, _global_tr), (mineable_tr, _mineable_global_tr)],
                                 ) = transforms.get_many_mut([e, *mineable_entity])
@@ -786,37 +1669,103 @@ fn move_units(
                                             face_dir.face_to_pos = mineable_tr.translation;
                                         }
                                     }
-                                    let diff_vec = mineable_tr.translation - tr.translation;
-                                    if diff_vec.length() > 110.0 {
-                                        tr.translation +=
-                                            diff_vec.normalize() * vel.0 * time.delta_seconds();
+                                    mining_comp.work_area = Some(mineable_tr.translation);
+                                    let mut work_pos = mineable_tr.translation;
+                                    let mut approach_dist = 110.0;
+                                    let mut mining_dist = 130.0;
+                                    if let Some(slots) = slots.as_mut() {
+                                        if let Some(slot) = slots.claim(e) {
+                                            work_pos += slots.offset(slot);
+                                            approach_dist = 2.0;
+                                            mining_dist = 20.0;
+                                        } else {
+                                            //Every slot is taken, wait next to the asteroid
+                                            approach_dist = slots.radius + 120.0;
+                                            mining_dist = 0.0;
+                                        }
+                                    }
+                                    let diff_vec = work_pos - tr.translation;
+                                    if diff_vec.length() > approach_dist {
+                                        tr.translation += diff_vec.normalize()
+                                            * vel.0
+                                            * time
+                                                .delta_seconds()
+                                                .min(diff_vec.length() / vel.0.max(1.0));
                                     }
 
-                                    if diff_vec.length() < 130.0
+                                    if diff_vec.length() < mining_dist
                                         && mining_comp.time_between_mine.finished()
                                         && mineable.amount > 0.
                                     {
                                         mining_comp.time_between_mine.reset();
                                         mining_comp.current_carry += 1.0;
+                                        mining_comp.carried_kind = Some(mineable.kind);
                                         mineable.amount -= 1.0;
                                     }
                                 }
+                            } else if let Some(depot_e) =
+                                nearest_depot(e, &transforms, &depots, &team_q)
+                            {
+                                *command =
+                                    UnitCommand::ReturnCargoToUnit(depot_e, Some(*mineable_entity));
                             } else {
-                                for mother_unit_e in mother_unit.iter() {
-                                    *command = UnitCommand::ReturnCargoToUnit(
-                                        mother_unit_e,
-                                        Some(*mineable_entity),
-                                    );
-                                    break;
-                                }
+                                //Nowhere to unload, the HUD warns about the stranded cargo
+                                *command = UnitCommand::Completed;
                             }
                         } else {
-                            //RETURN TO MOTHER WHEN ASTEROID IS OFF
-                            for mother_unit_e in mother_unit.iter() {
-                                *command = UnitCommand::ReturnCargoToUnit(mother_unit_e, None);
+                            //RETURN TO DEPOT WHEN ASTEROID IS OFF
+                            if mining_comp.current_carry <= 0. {
+                                *command = UnitCommand::Completed;
+                            } else if let Some(depot_e) =
+                                nearest_depot(e, &transforms, &depots, &team_q)
+                            {
+                                *command = UnitCommand::ReturnCargoToUnit(depot_e, None);
+                            } else {
+                                *command = UnitCommand::Completed;
+                            }
+                        }
+                    } else {
+                        *command = UnitCommand::Completed;
+                    }
+                }
+                UnitCommand::RepairEntity(repair_target) => {
+                    let mut repair_range = 100.0;
+                    if let Ok(repair_comp) = repair_q.get(e) {
+                        repair_range = repair_comp.range * 0.8;
+                    }
+                    if let Ok([(mut tr, _global_tr), (target_tr, _target_global_tr)]) =
+                        transforms.get_many_mut([e, *repair_target])
+                    {
+                        let diff_vec = target_tr.translation - tr.translation;
+                        for child in children {
+                            if let Ok(mut face_dir) = face_direction_q.get_mut(*child) {
+                                face_dir.face_to_pos = target_tr.translation;
+                                break;
+                            }
+                        }
+                        if diff_vec.length() > repair_range {
+                            tr.translation += diff_vec.normalize() * vel.0 * time.delta_seconds();
+                        }
+                    } else {
+                        *command = UnitCommand::Completed;
+                    }
+                }
+                UnitCommand::BuildStructure(site) => {
+                    if construction_q.get(*site).is_err() {
+                        *command = UnitCommand::Completed;
+                    } else if let Ok([(mut tr, _global_tr), (site_tr, _site_global_tr)]) =
+                        transforms.get_many_mut([e, *site])
+                    {
+                        let diff_vec = site_tr.translation - tr.translation;
+                        for child in children {
+                            if let Ok(mut face_dir) = face_direction_q.get_mut(*child) {
+                                face_dir.face_to_pos = site_tr.translation;
                                 break;
                             }
                         }
+                        if diff_vec.length() > BUILD_RANGE * 0.8 {
+                            tr.translation += diff_vec.normalize() * vel.0 * time.delta_seconds();
+                        }
                     } else {
                         *command = UnitCommand::Completed;
                     }
@@ -835,8 +1784,11 @@ fn move_units(
                             tr.translation += diff_vec.normalize() * vel.0 * time.delta_seconds();
                         } else {
                             if let Ok(mut mining_comp) = mining_component_q.get_mut(e) {
-                                mineral_resources.mineral += mining_comp.current_carry;
+                                if let Some(kind) = mining_comp.carried_kind {
+                                    ledger.deposit(kind, mining_comp.current_carry);
+                                }
                                 mining_comp.current_carry = 0.0;
+                                mining_comp.carried_kind = None;
                                 if let Some(last_mine) = last_mineable {
                                     *command = UnitCommand::MineEntity(*last_mine);
                                 } else {
@@ -855,37 +1807,234 @@ fn move_units(
     }
 }
 
+//Finished cargo depots, where miners can drop off their cargo
+type DepotQuery<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static GlobalTransform, &'static Team),
+    (With<CargoDepot>, Without<UnderConstruction>),
+>;
+
+//Closest finished depot of the miner's own team
+fn nearest_depot(
+    miner: Entity,
+    transforms: &Query<(&mut Transform, &GlobalTransform)>,
+    depots: &DepotQuery,
+    team_q: &Query<&Team>,
+) -> Option<Entity> {
+    let (_, miner_global_tr) = transforms.get(miner).ok()?;
+    let miner_team = team_q.get(miner).ok()?;
+    let mut closest_depot: (Option<Entity>, f32) = (None, f32::MAX);
+    for (depot_e, depot_global_tr, depot_team) in depots.iter() {
+        if depot_team.0 != miner_team.0 {
+            continue;
+        }
+        let dist = (depot_global_tr.translation() - miner_global_tr.translation()).length();
+        if dist < closest_depot.1 {
+            closest_depot = (Some(depot_e), dist);
+        }
+    }
+    closest_depot.0
+}
+
 #[derive(Component)]
 pub struct FlyingBullet {
     target: Entity,
     damage: f32,
     speed: f32,
     shooter: Entity,
+    shooter_team: Option<i32>,
+    direction: Vec3,
+    range_left: f32,
+    kind: ProjectileKind,
+    damage_type: DamageType,
+    splash: Option<Splash>,
+}
+
+#[derive(Component)]
+pub struct Explosion {
+    lifetime: Timer,
+}
+
+//What a bullet can run into and who it is allowed to hurt
+#[derive(SystemParam)]
+struct BulletHits<'w, 's> {
+    rapier_context: Res<'w, RapierContext>,
+    targets: Query<'w, 's, &'static Transform, Without<FlyingBullet>>,
+    team_q: Query<'w, 's, &'static Team>,
+    diplomacy: Res<'w, Diplomacy>,
+}
+
+fn explode(
+    cmd: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    hits: &BulletHits,
+    damage_event_writer: &mut EventWriter<DamageEvent>,
+    bullet: &FlyingBullet,
+    splash: Splash,
+    pos: Vec3,
+) {
+    let shape = Collider::ball(splash.radius);
+    hits.rapier_context.intersections_with_shape(
+        pos.truncate(),
+        0.,
+        &shape,
+        QueryFilter::default(),
+        |hit_e| {
+            let mut can_hit = hit_e == bullet.target;
+            if let (Some(shooter_team), Ok(hit_team)) =
+                (bullet.shooter_team, hits.team_q.get(hit_e))
+            {
+                can_hit = hits.diplomacy.is_hostile(shooter_team, hit_team.0)
+                    || (splash.friendly_fire && hit_e != bullet.shooter);
+            }
+            if can_hit {
+                let mut falloff = 1.0;
+                if let Ok(hit_tr) = hits.targets.get(hit_e) {
+                    let dist = (hit_tr.translation - pos).truncate().length();
+                    falloff = (1.0 - dist / splash.radius).clamp(0.25, 1.0);
+                }
+                damage_event_writer.send(DamageEvent {
+                    target: hit_e,
+                    dmg_amount: bullet.damage * falloff,
+                    damager: Some(bullet.shooter),
+                    dmg_type: bullet.damage_type,
+                });
+            }
+            true
+        },
+    );
+
+    spawn_explosion_visual(cmd, asset_server, pos, splash.radius);
+}
+
+pub fn spawn_explosion_visual(
+    cmd: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    pos: Vec3,
+    radius: f32,
+) {
+    cmd.spawn(SpriteBundle {
+        texture: asset_server.load("units/meteor_small.png"),
+        transform: Transform::from_translation(pos.truncate().extend(1.)),
+        sprite: Sprite {
+            color: Color::srgba(1., 0.6, 0.1, 0.8),
+            custom_size: Some(Vec2::splat(radius * 2.)),
+            ..default()
+        },
+        ..Default::default()
+    })
+    .insert(Explosion {
+        lifetime: Timer::from_seconds(0.4, TimerMode::Once),
+    });
+}
+
+fn fade_explosions(
+    time: Res<Time>,
+    mut explosions: Query<(&mut Explosion, &mut Sprite, Entity)>,
+    mut cmd: Commands,
+) {
+    for (mut explosion, mut sprite, e) in explosions.iter_mut() {
+        explosion.lifetime.tick(time.delta());
+        sprite
+            .color
+            .set_alpha(0.8 * explosion.lifetime.fraction_remaining());
+        if explosion.lifetime.finished() {
+            cmd.entity(e).despawn_recursive();
+        }
+    }
 }
 
 fn bullet_behaviour(
     time: Res<Time>,
-    mut bullets: Query<(&mut Transform, &FlyingBullet, Entity)>,
-    targets: Query<&Transform, Without<FlyingBullet>>,
+    mut bullets: Query<(&mut Transform, &mut FlyingBullet, Entity)>,
+    hits: BulletHits,
     mut damage_event_writer: EventWriter<DamageEvent>,
     mut cmd: Commands,
+    asset_server: Res<AssetServer>,
 ) {
-    for (mut bullet_tr, bullet, e) in bullets.iter_mut() {
-        if let Ok(target_tr) = targets.get(bullet.target) {
-            let diff_vec = (target_tr.translation - Vec3::new(0., 0., 1.)) - bullet_tr.translation;
-            if diff_vec.length() > 40. {
-                bullet_tr.translation +=
-                    diff_vec.normalize_or_zero() * time.delta_seconds() * bullet.speed;
+    for (mut bullet_tr, mut bullet, e) in bullets.iter_mut() {
+        //The entity that was hit (if any) and where the bullet detonated
+        let mut impact = None;
+        if bullet.kind == ProjectileKind::Homing {
+            if let Ok(target_tr) = hits.targets.get(bullet.target) {
+                let diff_vec =
+                    (target_tr.translation - Vec3::new(0., 0., 1.)) - bullet_tr.translation;
+                if diff_vec.length() > 40. {
+                    bullet.direction = diff_vec.truncate().extend(0.).normalize_or_zero();
+                    bullet_tr.translation +=
+                        diff_vec.normalize_or_zero() * time.delta_seconds() * bullet.speed;
+                } else {
+                    impact = Some((Some(bullet.target), bullet_tr.translation));
+                }
+            } else {
+                //Target is gone, the missile keeps flying on its last heading
+                bullet.kind = ProjectileKind::Ballistic;
+                cmd.entity(e).remove::<FaceMovementDirection>();
+            }
+        }
+
+        if bullet.kind == ProjectileKind::Ballistic {
+            let step = bullet.speed * time.delta_seconds();
+            let shooter_team = bullet.shooter_team;
+            let intended_target = bullet.target;
+            let shooter = bullet.shooter;
+            let can_hit = |hit_e: Entity| {
+                if hit_e == shooter {
+                    return false;
+                }
+                if hit_e == intended_target {
+                    return true;
+                }
+                match (shooter_team, hits.team_q.get(hit_e)) {
+                    (Some(shooter_t), Ok(hit_team)) => {
+                        hits.diplomacy.is_hostile(shooter_t, hit_team.0)
+                    }
+                    _ => false,
+                }
+            };
+            let filter = QueryFilter::default().predicate(&can_hit);
+            if let Some((hit_e, toi)) = hits.rapier_context.cast_ray(
+                bullet_tr.translation.truncate(),
+                bullet.direction.truncate(),
+                step,
+                true,
+                filter,
+            ) {
+                impact = Some((Some(hit_e), bullet_tr.translation + bullet.direction * toi));
             } else {
-                cmd.entity(e).despawn_recursive();
+                bullet_tr.translation += bullet.direction * step;
+                bullet.range_left -= step;
+                if bullet.range_left <= 0. {
+                    if bullet.splash.is_some() {
+                        impact = Some((None, bullet_tr.translation));
+                    } else {
+                        cmd.entity(e).despawn_recursive();
+                    }
+                }
+            }
+        }
+
+        if let Some((hit_e, impact_pos)) = impact {
+            cmd.entity(e).despawn_recursive();
+            if let Some(splash) = bullet.splash {
+                explode(
+                    &mut cmd,
+                    &asset_server,
+                    &hits,
+                    &mut damage_event_writer,
+                    &bullet,
+                    splash,
+                    impact_pos,
+                );
+            } else if let Some(hit_e) = hit_e {
                 damage_event_writer.send(DamageEvent {
-                    target: bullet.target,
+                    target: hit_e,
                     dmg_amount: bullet.damage,
-                    damager: bullet.shooter,
+                    damager: Some(bullet.shooter),
+                    dmg_type: bullet.damage_type,
                 });
             }
-        } else {
-            cmd.entity(e).despawn_recursive();
         }
     }
 }
@@ -950,6 +2099,36 @@ fn display_command_of_selection(
                             }
                         }
                     }
+                    UnitCommand::RepairEntity(repair_target) => {
+                        if let Some(mut highlighter_tr) = all_highlighters.next() {
+                            if let Ok(target_tr) = q_tr.get(*repair_target) {
+                                highlighter_tr.translation =
+                                    target_tr.translation - Vec3::new(0., 0., 1.);
+                                if let Some(last_p) = last_pos {
+                                    gizmos.linestrip(
+                                        [last_p, target_tr.translation],
+                                        Color::srgba(0., 0.6, 1., 0.3),
+                                    );
+                                }
+                                last_pos = Some(target_tr.translation);
+                            }
+                        }
+                    }
+                    UnitCommand::BuildStructure(site) => {
+                        if let Some(mut highlighter_tr) = all_highlighters.next() {
+                            if let Ok(site_tr) = q_tr.get(*site) {
+                                highlighter_tr.translation =
+                                    site_tr.translation - Vec3::new(0., 0., 1.);
+                                if let Some(last_p) = last_pos {
+                                    gizmos.linestrip(
+                                        [last_p, site_tr.translation],
+                                        Color::srgba(1., 0.8, 0., 0.3),
+                                    );
+                                }
+                                last_pos = Some(site_tr.translation);
+                            }
+                        }
+                    }
                     UnitCommand::MineEntity(mineable_entity) => {
                         if let Some(mut highlighter_tr) = all_highlighters.next() {
                             if let Ok(enemy_tr) = q_tr.get(*mineable_entity) {
@@ -984,80 +2163,578 @@ fn display_command_of_selection(
                 }
             }
         }
-    }
-}
+    }
+}
+
+#[derive(Component)]
+pub struct CommandHighlighter;
+
+fn spawn_command_highlighters(mut cmd: Commands, asset_server: Res<AssetServer>) {
+    for _ in 0..64 {
+        cmd.spawn(SpriteBundle {
+            transform: Transform::from_translation(Vec3::new(1000000., 9999999., -1.)),
+            texture: asset_server.load("icon_plusLarge.png"),
+            sprite: Sprite {
+                color: Color::srgba(0., 1., 0., 0.1),
+                ..default()
+            },
+            ..Default::default()
+        })
+        .insert(CommandHighlighter)
+        .insert(DontDestroyOnLoad);
+    }
+}
+
+#[derive(Component)]
+pub struct HealthBar;
+
+#[derive(Component)]
+pub struct ShieldBar;
+
+//Absorbs damage before Health, recharges after not being hit for a while
+#[derive(Component)]
+pub struct Shield {
+    pub current: f32,
+    pub capacity: f32,
+    pub regen_rate: f32,
+    pub regen_delay: Timer,
+}
+
+#[derive(Component)]
+pub struct Health {
+    pub current: f32,
+    pub max_health: f32,
+}
+#[derive(Event)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub dmg_amount: f32,
+    //None for environmental damage like asteroid impacts
+    pub damager: Option<Entity>,
+    pub dmg_type: DamageType,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DamageType {
+    Kinetic,
+    Energy,
+    Explosive,
+}
+
+//Fraction of incoming damage that is absorbed per damage type, negative values mean weakness
+#[derive(Component)]
+pub struct Armor {
+    pub kinetic: f32,
+    pub energy: f32,
+    pub explosive: f32,
+}
+
+impl Armor {
+    pub fn resistance(&self, dmg_type: DamageType) -> f32 {
+        match dmg_type {
+            DamageType::Kinetic => self.kinetic,
+            DamageType::Energy => self.energy,
+            DamageType::Explosive => self.explosive,
+        }
+    }
+}
+
+fn update_health_bars(
+    mut health_q: Query<(&mut Health, &Children, Option<&Shield>)>,
+    mut healthbar_q: Query<&mut Transform, (With<HealthBar>, Without<ShieldBar>)>,
+    mut shieldbar_q: Query<&mut Transform, (With<ShieldBar>, Without<HealthBar>)>,
+) {
+    for (health, children, shield) in health_q.iter_mut() {
+        for c in children.iter() {
+            if let Ok(mut bar) = healthbar_q.get_mut(*c) {
+                bar.scale = Vec3::new(health.current / health.max_health, 1., 1.);
+            }
+            if let Some(shield) = shield {
+                if let Ok(mut bar) = shieldbar_q.get_mut(*c) {
+                    bar.scale = Vec3::new(shield.current / shield.capacity, 1., 1.);
+                }
+            }
+        }
+    }
+}
+
+fn regenerate_shields(time: Res<Time>, mut shield_q: Query<&mut Shield>) {
+    for mut shield in shield_q.iter_mut() {
+        shield.regen_delay.tick(time.delta());
+        if shield.regen_delay.finished() && shield.current < shield.capacity {
+            shield.current =
+                (shield.current + shield.regen_rate * time.delta_seconds()).min(shield.capacity);
+        }
+    }
+}
+
+fn process_damage_events(
+    mut ev_damage: EventReader<DamageEvent>,
+    mut defenses: Query<(&mut Health, Option<&Armor>, Option<&mut Shield>)>,
+    mut unit_commands: Query<&mut UnitCommandList>,
+    team_q: Query<&Team>,
+    mut diplomacy: ResMut<Diplomacy>,
+    mut cmd: Commands,
+    mut veterancy_q: Query<&mut Veterancy>,
+) {
+    for dmg_event in ev_damage.read() {
+        if let Ok((mut hp, armor, shield)) = defenses.get_mut(dmg_event.target) {
+            let mut dmg_amount = dmg_event.dmg_amount;
+            if let Some(armor) = armor {
+                dmg_amount *= 1.0 - armor.resistance(dmg_event.dmg_type);
+            }
+            dmg_amount = dmg_amount.max(0.);
+            let dmg_dealt = dmg_amount.min(hp.current + shield.as_ref().map_or(0., |s| s.current));
+            if let Some(mut shield) = shield {
+                shield.regen_delay.reset();
+                let absorbed = dmg_amount.min(shield.current);
+                shield.current -= absorbed;
+                dmg_amount -= absorbed;
+            }
+            hp.current -= dmg_amount;
+            hp.current = hp.current.clamp(0., hp.max_health);
+            //Environmental damage takes the kill away from whoever hit the unit before
+            let Some(damager) = dmg_event.damager else {
+                if let Some(mut target_cmd) = cmd.get_entity(dmg_event.target) {
+                    target_cmd.remove::<LastDamagedBy>();
+                }
+                continue;
+            };
+            if let Some(mut target_cmd) = cmd.get_entity(dmg_event.target) {
+                target_cmd.try_insert(LastDamagedBy(damager));
+            }
+            let mut retaliate = true;
+            if let Ok([target_team, damager_team]) = team_q.get_many([dmg_event.target, damager]) {
+                //Neutrals turn hostile once shots are fired, allies never shoot back
+                if diplomacy.relation(target_team.0, damager_team.0) == Relation::Neutral {
+                    diplomacy.set_relation(target_team.0, damager_team.0, Relation::Hostile);
+                }
+                retaliate = !diplomacy.is_ally(target_team.0, damager_team.0);
+                if diplomacy.is_hostile(target_team.0, damager_team.0) {
+                    if let Ok(mut veterancy) = veterancy_q.get_mut(damager) {
+                        veterancy.xp += dmg_dealt * XP_PER_DAMAGE;
+                    }
+                }
+            }
+            if let Ok(mut unit_command) = unit_commands.get_mut(dmg_event.target) {
+                if retaliate && unit_command.commands.len() == 0 {
+                    unit_command
+                        .commands
+                        .push(UnitCommand::AttackEntity(damager));
+                }
+            }
+        }
+    }
+}
+
+type DyingUnits<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Health,
+        Entity,
+        &'static Transform,
+        Option<&'static Team>,
+        Option<&'static UnitArchetype>,
+        Option<&'static LastDamagedBy>,
+        Has<Drone>,
+    ),
+    Without<Asteroid>,
+>;
+
+//Asteroids break apart instead, see fracture_asteroids
+fn check_dead_units(
+    mut cmd: Commands,
+    health: DyingUnits,
+    mut ev_destroyed: EventWriter<UnitDestroyed>,
+) {
+    for (hp, e, tr, team, archetype, last_damager, drone) in health.iter() {
+        if hp.current <= 0. {
+            ev_destroyed.send(UnitDestroyed {
+                entity: e,
+                team: team.map(|t| t.0),
+                killer: last_damager.map(|d| d.0),
+                position: tr.translation,
+                archetype: archetype.copied(),
+                drone,
+            });
+            cmd.entity(e).despawn_recursive();
+        }
+    }
+}
+
+fn explode_destroyed_units(
+    mut cmd: Commands,
+    mut ev_destroyed: EventReader<UnitDestroyed>,
+    asset_server: Res<AssetServer>,
+) {
+    for destroyed in ev_destroyed.read() {
+        if destroyed.archetype.is_some() {
+            spawn_explosion_visual(&mut cmd, &asset_server, destroyed.position, 60.);
+        }
+    }
+}
+
+fn count_kills(
+    mut ev_destroyed: EventReader<UnitDestroyed>,
+    mut kill_stats: ResMut<KillStats>,
+    team_q: Query<&Team>,
+) {
+    for destroyed in ev_destroyed.read() {
+        if let Some(team) = destroyed.team {
+            *kill_stats.losses_by_team.entry(team).or_insert(0) += 1;
+        }
+        if let Some(killer_team) = destroyed.killer.and_then(|k| team_q.get(k).ok()) {
+            *kill_stats.kills_by_team.entry(killer_team.0).or_insert(0) += 1;
+        }
+    }
+}
+
+//Drop every order and selection that still points at a destroyed entity
+fn cleanup_dead_references(
+    mut ev_destroyed: EventReader<UnitDestroyed>,
+    mut command_lists: Query<&mut UnitCommandList>,
+    mut currently_selected: ResMut<CurrentlySelected>,
+) {
+    for destroyed in ev_destroyed.read() {
+        let dead = destroyed.entity;
+        currently_selected.ent.retain(|e| *e != dead);
+        for mut command_list in command_lists.iter_mut() {
+            command_list.commands.retain(|c| match c {
+                UnitCommand::AttackEntity(target)
+                | UnitCommand::MineEntity(target)
+                | UnitCommand::RepairEntity(target)
+                | UnitCommand::BuildStructure(target)
+                | UnitCommand::ReturnCargoToUnit(target, _) => *target != dead,
+                _ => true,
+            });
+            for c in command_list.commands.iter_mut() {
+                if let UnitCommand::ReturnCargoToUnit(_, last_mineable) = c {
+                    if *last_mineable == Some(dead) {
+                        *last_mineable = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+const XP_PER_DAMAGE: f32 = 1.0;
+const XP_PER_KILL_COST: f32 = 1.0;
+//Experience needed to reach each rank after the first
+const RANK_THRESHOLDS: [f32; 3] = [60., 180., 400.];
+const RANK_ATTACK_BONUS: f32 = 0.15;
+const RANK_FIRE_RATE_BONUS: f32 = 0.1;
+const RANK_HEALTH_BONUS: f32 = 0.1;
+
+#[derive(Component, Default)]
+pub struct Veterancy {
+    pub xp: f32,
+    pub rank: usize,
+}
+
+#[derive(Component)]
+pub struct RankInsignia;
+
+pub fn rank_chevrons(rank: usize) -> String {
+    "^".repeat(rank)
+}
+
+//Friendly fire kills are no reason to rank up
+fn award_kill_xp(
+    mut ev_destroyed: EventReader<UnitDestroyed>,
+    mut veterancy_q: Query<(&mut Veterancy, &Team)>,
+    diplomacy: Res<Diplomacy>,
+) {
+    for destroyed in ev_destroyed.read() {
+        if destroyed.drone {
+            continue;
+        }
+        if let (Some(killer), Some(archetype), Some(victim_team)) =
+            (destroyed.killer, destroyed.archetype, destroyed.team)
+        {
+            if let Ok((mut veterancy, killer_team)) = veterancy_q.get_mut(killer) {
+                if diplomacy.is_hostile(killer_team.0, victim_team) {
+                    veterancy.xp += archetype.cost() * XP_PER_KILL_COST;
+                }
+            }
+        }
+    }
+}
+
+fn rank_up_units(
+    mut cmd: Commands,
+    mut units: Query<(
+        Entity,
+        &mut Veterancy,
+        &mut AttackComponent,
+        &mut Health,
+        &Children,
+    )>,
+    mut insignia_q: Query<&mut Text, With<RankInsignia>>,
+) {
+    for (e, mut veterancy, mut attack_comp, mut hp, children) in units.iter_mut() {
+        let mut ranked_up = false;
+        while veterancy.rank < RANK_THRESHOLDS.len()
+            && veterancy.xp >= RANK_THRESHOLDS[veterancy.rank]
+        {
+            veterancy.rank += 1;
+            ranked_up = true;
+
+            attack_comp.attack_amount *= 1.0 + RANK_ATTACK_BONUS;
+            let faster = attack_comp
+                .time_between_attacks
+                .duration()
+                .mul_f32(1.0 - RANK_FIRE_RATE_BONUS);
+            attack_comp.time_between_attacks.set_duration(faster);
+            let bonus_health = hp.max_health * RANK_HEALTH_BONUS;
+            hp.max_health += bonus_health;
+            hp.current += bonus_health;
+        }
+        if !ranked_up {
+            continue;
+        }
+
+        let mut has_insignia = false;
+        for c in children.iter() {
+            if let Ok(mut text) = insignia_q.get_mut(*c) {
+                text.sections[0].value = rank_chevrons(veterancy.rank);
+                has_insignia = true;
+            }
+        }
+        if !has_insignia {
+            cmd.entity(e).with_children(|parent| {
+                parent
+                    .spawn(Text2dBundle {
+                        text: Text::from_section(
+                            rank_chevrons(veterancy.rank),
+                            TextStyle {
+                                font_size: 20.0,
+                                color: Color::srgb(1., 0.85, 0.2),
+                                ..default()
+                            },
+                        ),
+                        transform: Transform::from_translation(Vec3::new(0., 30., 1.)),
+                        ..default()
+                    })
+                    .insert(RankInsignia);
+            });
+        }
+    }
+}
+
+fn reset_kill_stats(mut kill_stats: ResMut<KillStats>) {
+    *kill_stats = KillStats::default();
+}
+
+//Only finished ships and structures, sites are healed by progress_construction
+type RepairPatients<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Health,
+        &'static Transform,
+        &'static Team,
+        Has<Structure>,
+    ),
+    (
+        With<UnitArchetype>,
+        Without<UnderConstruction>,
+        Without<Mineable>,
+    ),
+>;
+
+fn repair_units(
+    time: Res<Time>,
+    mut repairers: Query<(
+        Entity,
+        &RepairComponent,
+        &mut UnitCommandList,
+        &Transform,
+        &Team,
+    )>,
+    mut patients: RepairPatients,
+    mut ledger: ResMut<ResourceLedger>,
+    diplomacy: Res<Diplomacy>,
+) {
+    for (repairer_e, repair_comp, mut command_list, repairer_tr, repairer_team) in
+        repairers.iter_mut()
+    {
+        let heal_budget = repair_comp.heal_per_second * time.delta_seconds();
+        if repair_comp.stationary {
+            if !repair_comp.auto_cast {
+                continue;
+            }
+            //The dock only takes ships, structures have to be fixed by repair ships
+            for (patient_e, mut hp, patient_tr, patient_team, is_structure) in patients.iter_mut() {
+                if patient_e == repairer_e
+                    || is_structure
+                    || !diplomacy.is_ally(repairer_team.0, patient_team.0)
+                    || hp.current >= hp.max_health
+                    || (patient_tr.translation - repairer_tr.translation).length()
+                        > repair_comp.range
+                {
+                    continue;
+                }
+                let heal = heal_budget.min(hp.max_health - hp.current);
+                if ledger.spend(&ResourceCost::new(heal * repair_comp.ore_per_hp, 0., 0.)) {
+                    hp.current += heal;
+                }
+            }
+            continue;
+        }
 
-#[derive(Component)]
-pub struct CommandHighlighter;
+        if command_list.commands.is_empty() {
+            if !repair_comp.auto_cast {
+                continue;
+            }
+            //Look for the most damaged ally nearby
+            let mut most_damaged: (Option<Entity>, f32) = (None, 1.0);
+            for (patient_e, hp, patient_tr, patient_team, _) in patients.iter() {
+                let hp_fraction = hp.current / hp.max_health;
+                if patient_e != repairer_e
+                    && diplomacy.is_ally(repairer_team.0, patient_team.0)
+                    && hp_fraction < most_damaged.1
+                    && (patient_tr.translation - repairer_tr.translation).length()
+                        < repair_comp.range * 4.0
+                {
+                    most_damaged = (Some(patient_e), hp_fraction);
+                }
+            }
+            if let Some(patient_e) = most_damaged.0 {
+                command_list
+                    .commands
+                    .push(UnitCommand::RepairEntity(patient_e));
+            }
+            continue;
+        }
 
-fn spawn_command_highlighters(mut cmd: Commands, asset_server: Res<AssetServer>) {
-    for _ in 0..64 {
-        cmd.spawn(SpriteBundle {
-            transform: Transform::from_translation(Vec3::new(1000000., 9999999., -1.)),
-            texture: asset_server.load("icon_plusLarge.png"),
-            sprite: Sprite {
-                color: Color::srgba(0., 1., 0., 0.1),
-                ..default()
-            },
-            ..Default::default()
-        })
-        .insert(CommandHighlighter)
-        .insert(DontDestroyOnLoad);
+        if let UnitCommand::RepairEntity(patient_e) = command_list.commands[0] {
+            if let Ok((_, mut hp, patient_tr, _, _)) = patients.get_mut(patient_e) {
+                if hp.current >= hp.max_health {
+                    command_list.commands[0] = UnitCommand::Completed;
+                } else if (patient_tr.translation - repairer_tr.translation).length()
+                    <= repair_comp.range
+                {
+                    let heal = heal_budget.min(hp.max_health - hp.current);
+                    if ledger.spend(&ResourceCost::new(heal * repair_comp.ore_per_hp, 0., 0.)) {
+                        hp.current += heal;
+                    }
+                }
+            } else {
+                command_list.commands[0] = UnitCommand::Completed;
+            }
+        }
     }
 }
 
-#[derive(Component)]
-pub struct HealthBar;
-
-#[derive(Component)]
-pub struct Health {
-    pub current: f32,
-    pub max_health: f32,
-}
-#[derive(Event)]
-pub struct DamageEvent {
-    target: Entity,
-    dmg_amount: f32,
-    damager: Entity,
+fn toggle_automation(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    currently_selected: Res<CurrentlySelected>,
+    mut repair_q: Query<&mut RepairComponent>,
+    mut mining_q: Query<&mut MiningComponent>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyF) {
+        for e in currently_selected.ent.iter() {
+            if let Ok(mut repair_comp) = repair_q.get_mut(*e) {
+                repair_comp.auto_cast = !repair_comp.auto_cast;
+            }
+            if let Ok(mut mining_comp) = mining_q.get_mut(*e) {
+                mining_comp.auto_mine = !mining_comp.auto_mine;
+            }
+        }
+    }
 }
 
-fn update_health_bars(
-    mut health_q: Query<(&mut Health, &Children)>,
-    mut healthbar_q: Query<&mut Transform, With<HealthBar>>,
+//Miners waiting for a slot move on to a nearby asteroid that still has room
+fn seek_free_slots(
+    mut miners: Query<(Entity, &MiningComponent, &mut UnitCommandList, &Transform)>,
+    mineables: Query<(Entity, &Mineable, &MiningSlots, &Transform)>,
 ) {
-    for (health, children) in health_q.iter_mut() {
-        for c in children.iter() {
-            if let Ok(mut bar) = healthbar_q.get_mut(*c) {
-                bar.scale = Vec3::new(health.current / health.max_health, 1., 1.);
+    for (miner_e, mining_comp, mut command_list, miner_tr) in miners.iter_mut() {
+        if !mining_comp.auto_mine {
+            continue;
+        }
+        let Some(UnitCommand::MineEntity(target)) = command_list.commands.first_mut() else {
+            continue;
+        };
+        let Ok((_, _, slots, _)) = mineables.get(*target) else {
+            continue;
+        };
+        if slots.slot_of(miner_e).is_some() || !slots.is_full() {
+            continue;
+        }
+        let mut closest: (Option<Entity>, f32) = (None, AUTO_MINE_RADIUS);
+        for (mineable_e, mineable, other_slots, mineable_tr) in mineables.iter() {
+            if mineable.amount <= 0. || other_slots.is_full() {
+                continue;
+            }
+            let dist = (mineable_tr.translation - miner_tr.translation).length();
+            if dist < closest.1 {
+                closest = (Some(mineable_e), dist);
             }
         }
+        if let Some(free_mineable) = closest.0 {
+            *target = free_mineable;
+        }
     }
 }
 
-fn process_damage_events(
-    mut ev_damage: EventReader<DamageEvent>,
-    mut health_q: Query<&mut Health>,
-    mut unit_commands: Query<&mut UnitCommandList>,
+//How far from its work area an idle miner will look for a new asteroid
+const AUTO_MINE_RADIUS: f32 = 800.;
+
+//Idle miners go back to work, preferring asteroids nobody else is mining
+fn auto_retarget_miners(
+    mut miners: Query<(&MiningComponent, &mut UnitCommandList, &Transform)>,
+    mineables: Query<(Entity, &Mineable, &Transform)>,
 ) {
-    for dmg_event in ev_damage.read() {
-        if let Ok(mut hp) = health_q.get_mut(dmg_event.target) {
-            hp.current -= dmg_event.dmg_amount;
-            hp.current = hp.current.clamp(0., hp.max_health);
-            if let Ok(mut unit_command) = unit_commands.get_mut(dmg_event.target) {
-                if unit_command.commands.len() == 0 {
-                    unit_command
-                        .commands
-                        .push(UnitCommand::AttackEntity(dmg_event.damager));
+    let mut claims: HashMap<Entity, usize> = HashMap::new();
+    for (_, command_list, _) in miners.iter() {
+        for c in command_list.commands.iter() {
+            match c {
+                UnitCommand::MineEntity(target)
+                | UnitCommand::ReturnCargoToUnit(_, Some(target)) => {
+                    *claims.entry(*target).or_insert(0) += 1;
                 }
+                _ => {}
             }
         }
     }
-}
 
-fn check_dead_units(mut cmd: Commands, health: Query<(&Health, Entity)>) {
-    for (hp, e) in health.iter() {
-        if hp.current <= 0. {
-            cmd.entity(e).despawn_recursive();
+    for (mining_comp, mut command_list, miner_tr) in miners.iter_mut() {
+        //A full hold waits for a depot instead of bouncing between asteroids
+        if !mining_comp.auto_mine
+            || !command_list.commands.is_empty()
+            || mining_comp.current_carry >= mining_comp.max_carry
+        {
+            continue;
+        }
+        let Some(work_area) = mining_comp.work_area else {
+            continue;
+        };
+        let mut best: Option<(Entity, usize, f32)> = None;
+        for (mineable_e, mineable, mineable_tr) in mineables.iter() {
+            if mineable.amount <= 0.
+                || (mineable_tr.translation - work_area).length() > AUTO_MINE_RADIUS
+            {
+                continue;
+            }
+            let claim_count = claims.get(&mineable_e).copied().unwrap_or(0);
+            let dist = (mineable_tr.translation - miner_tr.translation).length();
+            let better = match best {
+                None => true,
+                Some((_, best_claims, best_dist)) => {
+                    claim_count < best_claims || (claim_count == best_claims && dist < best_dist)
+                }
+            };
+            if better {
+                best = Some((mineable_e, claim_count, dist));
+            }
+        }
+        if let Some((target, _, _)) = best {
+            command_list.commands.push(UnitCommand::MineEntity(target));
+            *claims.entry(target).or_insert(0) += 1;
         }
     }
 }
@@ -1068,7 +2745,28 @@ fn tick_attack_timers(time: Res<Time>, mut attack_comps: Query<&mut AttackCompon
     }
 }
 
-fn spawn_ranged_ally(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<AssetServer>) {
+//Ships the mothership can build, None for anything else
+pub fn spawn_ally(
+    cmd: &mut Commands,
+    archetype: UnitArchetype,
+    spawn_pos: Vec3,
+    asset_server: &Res<AssetServer>,
+) -> Option<Entity> {
+    match archetype {
+        UnitArchetype::Miner => Some(spawn_miner_ally(cmd, spawn_pos, asset_server)),
+        UnitArchetype::Melee => Some(spawn_melee_ally(cmd, spawn_pos, asset_server)),
+        UnitArchetype::Ranged => Some(spawn_ranged_ally(cmd, spawn_pos, asset_server)),
+        UnitArchetype::Artillery => Some(spawn_artillery_ally(cmd, spawn_pos, asset_server)),
+        UnitArchetype::Repair => Some(spawn_repair_ally(cmd, spawn_pos, asset_server)),
+        _ => None,
+    }
+}
+
+fn spawn_ranged_ally(
+    cmd: &mut Commands,
+    spawn_pos: Vec3,
+    asset_server: &Res<AssetServer>,
+) -> Entity {
     let mut attack_timer = Timer::from_seconds(0.5, TimerMode::Once);
     attack_timer.tick(std::time::Duration::from_secs(1));
     cmd.spawn(SpatialBundle {
@@ -1086,16 +2784,33 @@ fn spawn_ranged_ally(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<Ass
         current: 150.,
         max_health: 150.,
     })
+    .insert(Armor {
+        kinetic: 0.1,
+        energy: 0.1,
+        explosive: 0.,
+    })
+    .insert(Shield {
+        current: 50.,
+        capacity: 50.,
+        regen_rate: 10.,
+        regen_delay: Timer::from_seconds(3.0, TimerMode::Once),
+    })
     .insert(Team(0))
+    .insert(UnitArchetype::Ranged)
+    .insert(Veterancy::default())
     .insert(AttackComponent {
         attack_range: 300.,
         attack_amount: 10.,
         time_between_attacks: attack_timer.clone(),
+        damage_type: DamageType::Kinetic,
+        projectile: ProjectileKind::Ballistic,
+        splash: None,
     })
     .insert(Avoidance {
         last_frame_pos: Vec3::ZERO,
         currently_avoiding: false,
     })
+    .insert(MotionTracker::default())
     .insert(MildAggression)
     .with_children(|parent| {
         parent
@@ -1117,6 +2832,17 @@ fn spawn_ranged_ally(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<Ass
                 ..Default::default()
             })
             .insert(HealthBar);
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("healthbar.png"),
+                transform: Transform::from_translation(Vec3::new(0., -40., 0.)),
+                sprite: Sprite {
+                    color: Color::srgba(0., 0.6, 1., 1.),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(ShieldBar);
         parent
             .spawn(SpriteBundle {
                 texture: asset_server.load("units/meteor_small.png"),
@@ -1128,10 +2854,118 @@ fn spawn_ranged_ally(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<Ass
                 ..Default::default()
             })
             .insert(RenderLayers::layer(1));
-    });
+    })
+    .id()
+}
+
+fn spawn_artillery_ally(
+    cmd: &mut Commands,
+    spawn_pos: Vec3,
+    asset_server: &Res<AssetServer>,
+) -> Entity {
+    let mut attack_timer = Timer::from_seconds(2.5, TimerMode::Once);
+    attack_timer.tick(std::time::Duration::from_secs(1));
+    cmd.spawn(SpatialBundle {
+        transform: Transform::from_translation(spawn_pos),
+        ..Default::default()
+    })
+    .insert(Collider::cuboid(25.0, 25.0))
+    .insert(Sensor)
+    .insert(Selectable)
+    .insert(Velocity(90.))
+    .insert(UnitCommandList {
+        commands: Vec::new(),
+    })
+    .insert(Health {
+        current: 100.,
+        max_health: 100.,
+    })
+    .insert(Armor {
+        kinetic: 0.1,
+        energy: 0.1,
+        explosive: 0.,
+    })
+    .insert(Shield {
+        current: 50.,
+        capacity: 50.,
+        regen_rate: 10.,
+        regen_delay: Timer::from_seconds(3.0, TimerMode::Once),
+    })
+    .insert(Team(0))
+    .insert(UnitArchetype::Artillery)
+    .insert(Veterancy::default())
+    .insert(AttackComponent {
+        attack_range: 450.,
+        attack_amount: 30.,
+        time_between_attacks: attack_timer.clone(),
+        damage_type: DamageType::Explosive,
+        projectile: ProjectileKind::Ballistic,
+        splash: Some(Splash {
+            radius: 100.,
+            friendly_fire: true,
+        }),
+    })
+    .insert(Avoidance {
+        last_frame_pos: Vec3::ZERO,
+        currently_avoiding: false,
+    })
+    .insert(MotionTracker::default())
+    .insert(MildAggression)
+    .with_children(|parent| {
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("units/ship_basic.png"),
+                sprite: Sprite {
+                    color: Color::srgb(0.6, 0.8, 1.),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(FaceMovementDirection {
+                face_to_pos: Vec3::ZERO,
+            });
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("healthbar.png"),
+                transform: Transform::from_translation(Vec3::new(0., -30., 0.)),
+                sprite: Sprite {
+                    color: Color::srgba(0., 1., 0., 1.),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(HealthBar);
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("healthbar.png"),
+                transform: Transform::from_translation(Vec3::new(0., -40., 0.)),
+                sprite: Sprite {
+                    color: Color::srgba(0., 0.6, 1., 1.),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(ShieldBar);
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("units/meteor_small.png"),
+                sprite: Sprite {
+                    color: Color::srgba(0., 1., 0., 1.),
+                    custom_size: Some(Vec2::new(100., 100.)),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(RenderLayers::layer(1));
+    })
+    .id()
 }
 
-fn spawn_miner_ally(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<AssetServer>) {
+fn spawn_miner_ally(
+    cmd: &mut Commands,
+    spawn_pos: Vec3,
+    asset_server: &Res<AssetServer>,
+) -> Entity {
     let mut attack_timer = Timer::from_seconds(0.5, TimerMode::Once);
     attack_timer.tick(std::time::Duration::from_secs(1));
     cmd.spawn(SpatialBundle {
@@ -1149,18 +2983,32 @@ fn spawn_miner_ally(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<Asse
         current: 150.,
         max_health: 150.,
     })
+    .insert(Armor {
+        kinetic: 0.2,
+        energy: -0.5,
+        explosive: 0.,
+    })
     .insert(Team(0))
+    .insert(UnitArchetype::Miner)
+    .insert(Veterancy::default())
     .insert(AttackComponent {
         attack_range: 50.,
         attack_amount: 10.,
         time_between_attacks: attack_timer.clone(),
+        damage_type: DamageType::Kinetic,
+        projectile: ProjectileKind::Ballistic,
+        splash: None,
     })
     .insert(Avoidance {
         last_frame_pos: Vec3::ZERO,
         currently_avoiding: false,
     })
+    .insert(MotionTracker::default())
     .insert(MiningComponent {
         current_carry: 0.0,
+        carried_kind: None,
+        work_area: None,
+        auto_mine: true,
         max_carry: 10.0,
         time_between_mine: Timer::from_seconds(0.25, TimerMode::Once),
     })
@@ -1195,10 +3043,104 @@ fn spawn_miner_ally(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<Asse
                 ..Default::default()
             })
             .insert(RenderLayers::layer(1));
-    });
+    })
+    .id()
+}
+
+fn spawn_repair_ally(
+    cmd: &mut Commands,
+    spawn_pos: Vec3,
+    asset_server: &Res<AssetServer>,
+) -> Entity {
+    let mut attack_timer = Timer::from_seconds(0.5, TimerMode::Once);
+    attack_timer.tick(std::time::Duration::from_secs(1));
+    cmd.spawn(SpatialBundle {
+        transform: Transform::from_translation(spawn_pos),
+        ..Default::default()
+    })
+    .insert(Collider::cuboid(25.0, 25.0))
+    .insert(Sensor)
+    .insert(Selectable)
+    .insert(Velocity(150.))
+    .insert(UnitCommandList {
+        commands: Vec::new(),
+    })
+    .insert(Health {
+        current: 100.,
+        max_health: 100.,
+    })
+    .insert(Armor {
+        kinetic: 0.2,
+        energy: -0.5,
+        explosive: 0.,
+    })
+    .insert(Team(0))
+    .insert(UnitArchetype::Repair)
+    .insert(Veterancy::default())
+    .insert(AttackComponent {
+        attack_range: 50.,
+        attack_amount: 2.,
+        time_between_attacks: attack_timer.clone(),
+        damage_type: DamageType::Kinetic,
+        projectile: ProjectileKind::Ballistic,
+        splash: None,
+    })
+    .insert(Avoidance {
+        last_frame_pos: Vec3::ZERO,
+        currently_avoiding: false,
+    })
+    .insert(MotionTracker::default())
+    .insert(RepairComponent {
+        heal_per_second: 15.,
+        range: 150.,
+        ore_per_hp: 0.1,
+        auto_cast: true,
+        stationary: false,
+    })
+    .with_children(|parent| {
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("units/station_A.png"),
+                sprite: Sprite {
+                    color: Color::srgb(0.5, 1., 0.6),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(FaceMovementDirection {
+                face_to_pos: Vec3::ZERO,
+            });
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("healthbar.png"),
+                transform: Transform::from_translation(Vec3::new(0., -30., 0.)),
+                sprite: Sprite {
+                    color: Color::srgba(0., 1., 0., 1.),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(HealthBar);
+        parent
+            .spawn(SpriteBundle {
+                texture: asset_server.load("units/meteor_small.png"),
+                sprite: Sprite {
+                    color: Color::srgba(0., 1., 0., 1.),
+                    custom_size: Some(Vec2::new(100., 100.)),
+                    ..default()
+                },
+                ..Default::default()
+            })
+            .insert(RenderLayers::layer(1));
+    })
+    .id()
 }
 
-fn spawn_melee_ally(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<AssetServer>) {
+fn spawn_melee_ally(
+    cmd: &mut Commands,
+    spawn_pos: Vec3,
+    asset_server: &Res<AssetServer>,
+) -> Entity {
     let mut attack_timer = Timer::from_seconds(0.75, TimerMode::Once);
     attack_timer.tick(std::time::Duration::from_secs(1));
     cmd.spawn(SpatialBundle {
@@ -1216,16 +3158,27 @@ fn spawn_melee_ally(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<Asse
         current: 200.,
         max_health: 200.,
     })
+    .insert(Armor {
+        kinetic: 0.3,
+        energy: 0.,
+        explosive: -0.2,
+    })
     .insert(Team(0))
+    .insert(UnitArchetype::Melee)
+    .insert(Veterancy::default())
     .insert(AttackComponent {
         attack_range: 100.,
         attack_amount: 10.,
         time_between_attacks: attack_timer.clone(),
+        damage_type: DamageType::Energy,
+        projectile: ProjectileKind::Ballistic,
+        splash: None,
     })
     .insert(Avoidance {
         last_frame_pos: Vec3::ZERO,
         currently_avoiding: false,
     })
+    .insert(MotionTracker::default())
     .insert(MildAggression)
     .with_children(|parent| {
         parent
@@ -1258,7 +3211,8 @@ fn spawn_melee_ally(cmd: &mut Commands, spawn_pos: Vec3, asset_server: &Res<Asse
                 ..Default::default()
             })
             .insert(RenderLayers::layer(1));
-    });
+    })
+    .id()
 }
 
 fn spawn_main_menu_units(mut cmd: Commands, asset_server: Res<AssetServer>) {